@@ -1,149 +1,119 @@
-use std::sync::Arc;
-
-use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
-use log::info;
-use rand::{rngs::StdRng, Rng, SeedableRng};
-use tokio::sync::{Mutex, RwLock};
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::Html,
+    routing::{get, post},
+    Router,
+};
+use log::{info, warn};
 
 use crate::{
-    config::{self, Config},
-    gamestateintegration::{MapPhase, Payload, RoundPhase},
-    pishock, AppState, GameState, PlayerState,
+    control,
+    feed::{self, ActionTrigger},
+    gamestateintegration::Payload,
+    metrics,
+    pishock::PiShockOp,
+    rules, status, AppState,
 };
 
-pub async fn run(config: Arc<RwLock<Config>>) {
+/// Serve the GSI endpoint on `127.0.0.1:<port>` until the process exits.
+pub async fn run(state: AppState, port: u16) {
     info!("Sending test beep");
-    pishock::beep(config.clone(), 1).await;
-
-    let state = AppState {
-        game_state: Arc::from(Mutex::from(GameState::default())),
-        config: config.clone(),
-    };
+    state
+        .perform(PiShockOp::Beep { duration: 1 }, ActionTrigger::Manual)
+        .await;
 
     let app = Router::new()
         .route("/data", post(read_data))
+        .route("/events", get(feed::stream))
+        .route("/overlay", get(overlay))
+        .route("/metrics", get(metrics::metrics))
+        .route("/status", get(status::status))
+        .nest("/api", control::router(state.clone()))
         .with_state(state);
 
-    info!("Starting server on {}", "127.0.0.1:3000");
+    let addr = format!("127.0.0.1:{}", port);
+    info!("Starting server on {}", addr);
 
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:3000")
-        .await
-        .unwrap();
+    let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
     axum::serve(listener, app).await.unwrap();
 }
 
-async fn read_data(State(state): State<AppState>, Json(payload): Json<Payload>) -> StatusCode {
-    let mut game_state = state.game_state.lock().await;
+async fn read_data(State(state): State<AppState>, headers: HeaderMap, body: String) -> StatusCode {
+    // CS2 always sends JSON. Anything else could be a web page posting a
+    // form or text/plain, which browsers send without asking first.
+    if !is_json(&headers) {
+        warn!("Rejected payload that is not application/json");
+        return StatusCode::UNSUPPORTED_MEDIA_TYPE;
+    }
+
+    state.metrics.payload_received();
+    state.health.lock().await.payload_received();
     let config = state.config.read().await;
 
-    if let Some(provider) = payload.provider {
-        game_state.steam_id = provider.steamid;
-    }
+    state.recorder.lock().await.record(&config.recording, &body);
+
+    let payload = match serde_json::from_str::<Payload>(&body) {
+        Ok(payload) => payload,
+        Err(e) => {
+            warn!("Failed to parse payload: {}", e);
+            state.metrics.parse_failed();
+            state
+                .health
+                .lock()
+                .await
+                .error(format!("Failed to parse payload: {}", e));
+            return StatusCode::BAD_REQUEST;
+        }
+    };
 
-    if let Some(map) = payload.map {
-        if game_state.map_phase == MapPhase::Warmup && map.phase == MapPhase::Live {
-            info!("Match started");
+    drop(config);
 
-            if config.beep_on_match_start {
-                pishock::beep(state.config.clone(), 2).await;
-            }
+    process_payload(&state, payload).await;
 
-            // Reset game state to default
-            game_state.reset();
-        }
+    StatusCode::OK
+}
 
-        game_state.map_phase = map.phase;
-    }
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/json"))
+}
 
-    if let Some(round) = payload.round {
-        if game_state.round_phase == RoundPhase::Freezetime && round.phase == RoundPhase::Live {
-            if config.beep_on_round_start {
-                info!("Round started");
-                pishock::beep(state.config.clone(), 1).await;
-            }
-        }
+/// `GET /overlay`, browser source for OBS showing actions from `/events`.
+async fn overlay() -> Html<&'static str> {
+    Html(include_str!("../assets/overlay.html"))
+}
 
-        game_state.round_phase = round.phase;
-    }
+/// Run a payload through event detection and the rules, shared by the
+/// HTTP endpoint and replays.
+pub async fn process_payload(state: &AppState, payload: Payload) {
+    let config = state.config.read().await;
+    let mut game_state = state.game_state.lock().await;
 
-    if game_state.map_phase != MapPhase::Live {
-        return StatusCode::OK;
-    }
+    let events = game_state.update(&payload, &config.follow);
+    let mode = game_state.game_mode.clone();
+    state.game_updates.send_replace(game_state.clone());
+    drop(game_state);
 
-    if let Some(player) = payload.player {
-        if player.steamid != game_state.steam_id {
-            return StatusCode::OK;
-        }
+    let mut actions = Vec::new();
+    for event in events {
+        info!("{}", event);
+        state.metrics.event(event.kind());
 
-        if let Some(player_state) = &mut game_state.player_state {
-            if player_state.health > player.state.health && player.state.health > 0 {
-                // Took damage and survived
-
-                /*
-                println!("Player took damage, vibrating");
-
-                let diff = player_state.health - player.state.health;
-
-                let res = pishock::post(
-                    &config,
-                    pishock::PiShockOp::Vibrate {
-                        intensity: diff,
-                        duration: 1,
-                    },
-                )
-                .await;
-
-                match res {
-                    Ok(code) => println!("Vibrated with code {}", code),
-                    Err(e) => println!("Error while vibrating: {}", e),
-                };
-                 */
-            }
-
-            if player.match_stats.deaths > player_state.deaths {
-                // Died
-
-                // Github Copilot is based
-                // let res = pishock::post(&api_state, pishock::PiShockOp::Shock { intensity: 100, duration: 1 }).await;
-
-                info!("Player died, shocking");
-
-                match config.shock_mode {
-                    config::ShockMode::Random => {
-                        let mut rng = StdRng::from_entropy();
-                        let intensity = rng.gen_range(config.min_intensity..=config.max_intensity);
-                        let duration = rng.gen_range(config.min_duration..=config.max_duration);
-
-                        pishock::shock(state.config.clone(), intensity, duration).await;
-                    }
-                    config::ShockMode::LastHitPercentage => {
-                        let intensity = (player_state.health as f32 / 100.0
-                            * config.max_intensity as f32)
-                            as i32;
-                        let duration = (player_state.health as f32 / 100.0
-                            * config.max_duration as f32)
-                            as i32;
-
-                        pishock::shock(state.config.clone(), intensity, duration).await;
-                    }
-                };
-            }
-
-            player_state.health = player.state.health;
-            player_state.armor = player.state.armor;
-            player_state.kills = player.match_stats.kills;
-            player_state.deaths = player.match_stats.deaths;
-        } else {
-            println!("Player state initialized");
-
-            game_state.player_state = Some(PlayerState {
-                health: player.state.health,
-                armor: player.state.armor,
-                kills: player.match_stats.kills,
-                deaths: player.match_stats.deaths,
-            });
+        let trigger = ActionTrigger::Event(event.kind().to_owned());
+        for op in rules::evaluate(&config, &mode, &event) {
+            actions.push((op, trigger.clone()));
         }
+        state.publish_event(event);
     }
+    // perform reads the config again, holding on to it here deadlocks as soon
+    // as a writer queues up in between
+    drop(config);
 
-    StatusCode::OK
+    for (op, trigger) in actions {
+        state.perform(op, trigger).await;
+    }
 }
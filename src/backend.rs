@@ -0,0 +1,24 @@
+use log::info;
+
+use crate::pishock::{self, Credentials, PiShockOp};
+
+/// Where device operations triggered by game events end up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// Send operations to the PiShock API
+    PiShock,
+    /// Only log what would have been sent
+    DryRun,
+}
+
+impl Backend {
+    pub async fn send(&self, credentials: Credentials, op: PiShockOp) -> Result<(), String> {
+        match self {
+            Backend::PiShock => pishock::send(credentials, op).await.map(|_| ()),
+            Backend::DryRun => {
+                info!(target: "Dry run", "Would send {:?}", op);
+                Ok(())
+            }
+        }
+    }
+}
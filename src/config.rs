@@ -1,16 +1,387 @@
-use std::{fs::OpenOptions, io::Write};
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
 
-use log::error;
+use log::{error, info, warn};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
+use crate::{
+    gamestateintegration::{GameMode, Weapon},
+    paths,
+    pishock::PiShockOp,
+    secrets::{self, Secret},
+};
+
+/// How the death shock intensity and duration are picked.
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 pub enum ShockMode {
     Random,
     LastHitPercentage,
 }
 
+/// Whose deaths are followed.
+///
+/// `provider.steamid` is always the client running CS2, while `player.steamid`
+/// is whoever that client is currently looking at. These differ while dead,
+/// spectating, coaching or casting.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default)]
+pub enum FollowMode {
+    /// Only the player running the client
+    #[default]
+    Own,
+    /// A specific player by SteamID64, whenever they are being observed
+    Player(String),
+    /// Whoever is currently observed, including yourself while alive
+    Observed,
+}
+
+impl FollowMode {
+    pub fn follows(&self, provider_steamid: &str, player_steamid: &str) -> bool {
+        match self {
+            FollowMode::Own => player_steamid == provider_steamid,
+            FollowMode::Player(steamid) => player_steamid == steamid.trim(),
+            FollowMode::Observed => true,
+        }
+    }
+}
+
+/// Operation a trigger sends, intensity is ignored for beeps.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerOp {
+    Beep,
+    Vibrate,
+    Shock,
+}
+
+impl TriggerOp {
+    pub fn with(self, intensity: i32, duration: i32) -> PiShockOp {
+        match self {
+            TriggerOp::Beep => PiShockOp::Beep { duration },
+            TriggerOp::Vibrate => PiShockOp::Vibrate {
+                intensity,
+                duration,
+            },
+            TriggerOp::Shock => PiShockOp::Shock {
+                intensity,
+                duration,
+            },
+        }
+    }
+}
+
+/// Fires on death when the loadout lost was worth at least `min_equip_value`.
+/// Intensity and duration scale from the configured min to max as the
+/// equip value approaches `max_equip_value`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct ExpensiveDeathTrigger {
+    pub enabled: bool,
+    pub op: TriggerOp,
+    pub min_equip_value: i32,
+    pub max_equip_value: i32,
+}
+
+impl Default for ExpensiveDeathTrigger {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            op: TriggerOp::Vibrate,
+            min_equip_value: 4000,
+            max_equip_value: 8000,
+        }
+    }
+}
+
+/// Fires when a new round's freezetime begins with less than `threshold` money.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct LowMoneyTrigger {
+    pub enabled: bool,
+    pub op: TriggerOp,
+    pub threshold: i32,
+    pub intensity: i32,
+    pub duration: i32,
+}
+
+impl Default for LowMoneyTrigger {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            op: TriggerOp::Beep,
+            threshold: 2000,
+            intensity: 1,
+            duration: 1,
+        }
+    }
+}
+
+/// Per game mode switches, `enabled` turns off everything including beeps.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct ModeFilter {
+    pub enabled: bool,
+    /// Percentage applied to death and economy punishments
+    pub scale: i32,
+    pub death: bool,
+    pub expensive_death: bool,
+    pub low_money: bool,
+}
+
+impl Default for ModeFilter {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            scale: 100,
+            death: true,
+            expensive_death: true,
+            low_money: true,
+        }
+    }
+}
+
+/// A [`ModeFilter`] for every [`GameMode`].
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct GameModeFilters {
+    pub competitive: ModeFilter,
+    pub casual: ModeFilter,
+    pub deathmatch: ModeFilter,
+    pub wingman: ModeFilter,
+    pub arms_race: ModeFilter,
+    pub other: ModeFilter,
+}
+
+impl Default for GameModeFilters {
+    fn default() -> Self {
+        // Dying is constant in respawn modes and there is no economy to train
+        let respawn = ModeFilter {
+            scale: 50,
+            expensive_death: false,
+            low_money: false,
+            ..Default::default()
+        };
+
+        Self {
+            competitive: ModeFilter::default(),
+            casual: ModeFilter::default(),
+            deathmatch: respawn.clone(),
+            wingman: ModeFilter::default(),
+            arms_race: respawn,
+            other: ModeFilter::default(),
+        }
+    }
+}
+
+impl GameModeFilters {
+    pub fn get(&self, mode: &GameMode) -> &ModeFilter {
+        match mode {
+            GameMode::Competitive => &self.competitive,
+            GameMode::Casual => &self.casual,
+            GameMode::Deathmatch => &self.deathmatch,
+            GameMode::Wingman => &self.wingman,
+            GameMode::ArmsRace => &self.arms_race,
+            GameMode::Other => &self.other,
+        }
+    }
+
+    /// Config key of a mode named by [`GameModeFilters::iter`].
+    pub fn field_name(name: &str) -> String {
+        name.to_lowercase().replace(' ', "_")
+    }
+
+    pub fn iter(&self) -> [(&'static str, &ModeFilter); 6] {
+        [
+            ("Competitive", &self.competitive),
+            ("Casual", &self.casual),
+            ("Deathmatch", &self.deathmatch),
+            ("Wingman", &self.wingman),
+            ("Arms Race", &self.arms_race),
+            ("Other", &self.other),
+        ]
+    }
+
+    pub fn iter_mut(&mut self) -> [(&'static str, &mut ModeFilter); 6] {
+        [
+            ("Competitive", &mut self.competitive),
+            ("Casual", &mut self.casual),
+            ("Deathmatch", &mut self.deathmatch),
+            ("Wingman", &mut self.wingman),
+            ("Arms Race", &mut self.arms_race),
+            ("Other", &mut self.other),
+        ]
+    }
+}
+
+/// Scales death punishments by the weapon held at the time of death.
+///
+/// `pattern` is matched case-insensitively against the weapon name, with or
+/// without the `weapon_` prefix (`awp`, `weapon_awp`), or its category
+/// (`Knife`, `Pistol`, `SniperRifle`, ...). A `scale` of 0 skips the
+/// punishment entirely.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct WeaponModifier {
+    pub pattern: String,
+    /// Percentage applied to intensity and duration
+    pub scale: i32,
+    /// Only apply when the equip value lost is at most this, e.g. for ecos
+    pub max_equip_value: Option<i32>,
+}
+
+impl Default for WeaponModifier {
+    fn default() -> Self {
+        Self {
+            pattern: String::new(),
+            scale: 100,
+            max_equip_value: None,
+        }
+    }
+}
+
+impl WeaponModifier {
+    pub fn matches(&self, weapon: &Weapon, equip_value: i32) -> bool {
+        if let Some(max_equip_value) = self.max_equip_value {
+            if equip_value > max_equip_value {
+                return false;
+            }
+        }
+
+        let pattern = self.pattern.trim().to_lowercase();
+        let name = weapon.name.to_lowercase();
+
+        pattern == name
+            || Some(pattern.as_str()) == name.strip_prefix("weapon_")
+            || pattern == weapon.kind.to_lowercase()
+    }
+}
+
+/// Raw GSI payload recording, see [`crate::recorder::Recorder`].
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct RecordingConfig {
+    pub enabled: bool,
+    pub directory: String,
+    pub max_file_size_mb: i32,
+    pub max_total_size_mb: i32,
+}
+
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            directory: "recordings".to_owned(),
+            max_file_size_mb: 10,
+            max_total_size_mb: 200,
+        }
+    }
+}
+
+impl RecordingConfig {
+    /// Where recordings go, a relative `directory` is next to the config
+    /// file rather than wherever cs2shock happened to be started from.
+    pub fn path(&self, config_path: Option<&Path>) -> PathBuf {
+        config_path
+            .and_then(Path::parent)
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .map(Path::to_owned)
+            .or_else(paths::config_dir)
+            .unwrap_or_default()
+            .join(&self.directory)
+    }
+}
+
+/// A problem with one setting. `field` is the path to it, like
+/// `low_money.duration` or `weapon_modifiers.0.pattern`, and names both
+/// ends of a range when they are out of order, like `intensity`.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    pub field: String,
+    pub message: String,
+}
+
+impl ValidationError {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.field, self.message)
+    }
+}
+
+/// Current layout of `config.json`. Bump it and add a step to [`MIGRATIONS`]
+/// whenever a field is renamed, moved or changes meaning.
+pub const CONFIG_VERSION: u32 = 3;
+
+/// Upgrades from each older layout to the next, `MIGRATIONS[0]` turns a
+/// version 1 config into version 2 and so on.
+const MIGRATIONS: [fn(&mut Map<String, Value>); CONFIG_VERSION as usize - 1] = [v1_to_v2, v2_to_v3];
+
+/// Version 1 is every config written before it had a version, nothing was
+/// renamed since then and the sections added later come from the defaults.
+fn v1_to_v2(_config: &mut Map<String, Value>) {}
+
+/// Version 3 keeps the credentials in `secrets.json`, they are still read
+/// from the old file and end up in the secrets file when it is rewritten.
+fn v2_to_v3(_config: &mut Map<String, Value>) {}
+
+/// Bring a raw config up to [`CONFIG_VERSION`], returning the version it had.
+fn migrate(value: &mut Value) -> Result<u32, String> {
+    let config = value
+        .as_object_mut()
+        .ok_or_else(|| "config is not a JSON object".to_owned())?;
+
+    let version = config
+        .get("version")
+        .and_then(Value::as_u64)
+        .map_or(1, |version| version.max(1) as u32);
+
+    if version > CONFIG_VERSION {
+        warn!(
+            target: "Config",
+            "Config is from a newer version ({}), settings this version does not know about will be lost when saving",
+            version
+        );
+    }
+
+    for migration in MIGRATIONS.iter().skip(version as usize - 1) {
+        migration(config);
+    }
+    config.insert("version".to_owned(), Value::from(CONFIG_VERSION));
+
+    Ok(version)
+}
+
+/// Copy `path` to `<path>.<suffix>.bak` before it gets overwritten.
+fn backup(path: &Path, suffix: &str) -> Result<PathBuf, String> {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(format!(".{}.bak", suffix));
+    let backup = PathBuf::from(backup);
+
+    fs::copy(path, &backup)
+        .and_then(|_| secrets::restrict(&backup))
+        .map_err(|e| format!("Failed to back up config to {}: {}", backup.display(), e))?;
+    Ok(backup)
+}
+
+/// Settings persisted to `config.json`.
+///
+/// Missing fields are filled in from [`Config::default`] and unknown fields
+/// are ignored, so a partial file never wipes the rest of the settings.
 #[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+#[serde(default)]
 pub struct Config {
+    /// Layout version, see [`CONFIG_VERSION`]
+    pub version: u32,
+    /// Active profile layered over this config, empty for none
+    pub profile: String,
     pub shock_mode: ShockMode,
     pub min_duration: i32,
     pub max_duration: i32,
@@ -18,14 +389,28 @@ pub struct Config {
     pub max_intensity: i32,
     pub beep_on_match_start: bool,
     pub beep_on_round_start: bool,
-    pub username: String,
-    pub code: String,
-    pub apikey: String,
+    pub follow: FollowMode,
+    pub expensive_death: ExpensiveDeathTrigger,
+    pub low_money: LowMoneyTrigger,
+    pub weapon_modifiers: Vec<WeaponModifier>,
+    pub game_modes: GameModeFilters,
+    pub recording: RecordingConfig,
+    /// Bearer token for the control API, the API is disabled while empty
+    #[serde(skip_serializing)]
+    pub api_token: Secret,
+    #[serde(skip_serializing)]
+    pub username: Secret,
+    #[serde(skip_serializing)]
+    pub code: Secret,
+    #[serde(skip_serializing)]
+    pub apikey: Secret,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            version: CONFIG_VERSION,
+            profile: String::new(),
             shock_mode: ShockMode::Random,
             min_duration: 1,
             max_duration: 1,
@@ -33,58 +418,587 @@ impl Default for Config {
             max_intensity: 1,
             beep_on_match_start: false,
             beep_on_round_start: false,
-            username: String::new(),
-            code: String::new(),
-            apikey: String::new(),
+            follow: FollowMode::Own,
+            expensive_death: ExpensiveDeathTrigger::default(),
+            low_money: LowMoneyTrigger::default(),
+            weapon_modifiers: Vec::new(),
+            game_modes: GameModeFilters::default(),
+            recording: RecordingConfig::default(),
+            api_token: Secret::default(),
+            username: Secret::default(),
+            code: Secret::default(),
+            apikey: Secret::default(),
         }
     }
 }
 impl Config {
-    pub fn validate(&self) -> bool {
-        if self.min_duration < 1 || self.min_duration > 15 {
-            error!(target: "Config", "min_duration must be between 1 and 15");
-            return false;
+    /// Load a config file, migrating it from older layouts. A migrated file is
+    /// backed up and rewritten, a file that cannot be parsed is backed up so
+    /// saving the defaults does not destroy it. Returns `Ok(None)` if the file
+    /// does not exist.
+    pub fn load(path: &Path) -> Result<Option<Config>, String> {
+        let raw = match fs::read_to_string(path) {
+            Ok(raw) => raw,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+
+        let parsed = serde_json::from_str::<Value>(&raw)
+            .map_err(|e| e.to_string())
+            .and_then(|mut value| {
+                let version = migrate(&mut value)?;
+                let config = serde_json::from_value::<Config>(value).map_err(|e| e.to_string())?;
+                Ok((version, config))
+            });
+
+        let (version, mut config) = match parsed {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                let backup = backup(path, "invalid")?;
+                return Err(format!(
+                    "Failed to parse {}, saved a copy as {}: {}",
+                    path.display(),
+                    backup.display(),
+                    e
+                ));
+            }
+        };
+
+        if let Err(e) = secrets::load(path, &mut config) {
+            error!(target: "Config", "{}", e);
+        }
+
+        if version < CONFIG_VERSION {
+            let backup = backup(path, &format!("v{}", version))?;
+            config.write_to_file(path)?;
+            info!(
+                target: "Config",
+                "Migrated config from version {} to {}, the old file was saved as {}",
+                version,
+                CONFIG_VERSION,
+                backup.display()
+            );
+        }
+
+        secrets::apply_env(&mut config);
+
+        Ok(Some(config))
+    }
+
+    /// Every problem with the config, empty when it is valid.
+    pub fn validate(&self) -> Vec<ValidationError> {
+        let mut errors = Vec::new();
+
+        if !(1..=15).contains(&self.min_duration) {
+            errors.push(ValidationError::new(
+                "min_duration",
+                "must be between 1 and 15",
+            ));
         }
 
-        if self.max_duration < 1 || self.max_duration > 15 {
-            error!(target: "Config", "max_duration must be between 1 and 15");
-            return false;
+        if !(1..=15).contains(&self.max_duration) {
+            errors.push(ValidationError::new(
+                "max_duration",
+                "must be between 1 and 15",
+            ));
         }
 
         if self.min_duration > self.max_duration {
-            error!(target: "Config", "min_duration must be less than or equal to max_duration");
-            return false;
+            errors.push(ValidationError::new(
+                "duration",
+                "min must be less than or equal to max",
+            ));
         }
 
-        if self.min_intensity < 0 || self.min_intensity > 100 {
-            error!(target: "Config", "min_intensity must be between 0 and 100");
-            return false;
+        if !(0..=100).contains(&self.min_intensity) {
+            errors.push(ValidationError::new(
+                "min_intensity",
+                "must be between 0 and 100",
+            ));
         }
 
-        if self.max_intensity < 0 || self.max_intensity > 100 {
-            error!(target: "Config", "max_intensity must be between 0 and 100");
-            return false;
+        if !(0..=100).contains(&self.max_intensity) {
+            errors.push(ValidationError::new(
+                "max_intensity",
+                "must be between 0 and 100",
+            ));
         }
 
         if self.min_intensity > self.max_intensity {
-            error!(target: "Config", "min_intensity must be less than or equal to max_intensity");
-            return false;
+            errors.push(ValidationError::new(
+                "intensity",
+                "min must be less than or equal to max",
+            ));
+        }
+
+        if let FollowMode::Player(steamid) = &self.follow {
+            let steamid = steamid.trim();
+            if steamid.is_empty() || !steamid.chars().all(|c| c.is_ascii_digit()) {
+                errors.push(ValidationError::new(
+                    "follow",
+                    "must be a numeric SteamID64",
+                ));
+            }
+        }
+
+        if self.expensive_death.min_equip_value < 0 {
+            errors.push(ValidationError::new(
+                "expensive_death.min_equip_value",
+                "must be at least 0",
+            ));
+        }
+
+        if self.expensive_death.min_equip_value >= self.expensive_death.max_equip_value {
+            errors.push(ValidationError::new(
+                "expensive_death.equip_value",
+                "min must be less than max",
+            ));
+        }
+
+        if self.low_money.threshold < 0 {
+            errors.push(ValidationError::new(
+                "low_money.threshold",
+                "must be at least 0",
+            ));
+        }
+
+        if !(0..=100).contains(&self.low_money.intensity) {
+            errors.push(ValidationError::new(
+                "low_money.intensity",
+                "must be between 0 and 100",
+            ));
+        }
+
+        if !(1..=15).contains(&self.low_money.duration) {
+            errors.push(ValidationError::new(
+                "low_money.duration",
+                "must be between 1 and 15",
+            ));
+        }
+
+        for (i, modifier) in self.weapon_modifiers.iter().enumerate() {
+            if modifier.pattern.trim().is_empty() {
+                errors.push(ValidationError::new(
+                    format!("weapon_modifiers.{}.pattern", i),
+                    "must not be empty",
+                ));
+            }
+
+            if !(0..=1000).contains(&modifier.scale) {
+                errors.push(ValidationError::new(
+                    format!("weapon_modifiers.{}.scale", i),
+                    "must be between 0 and 1000",
+                ));
+            }
+        }
+
+        for (name, filter) in self.game_modes.iter() {
+            if !(0..=1000).contains(&filter.scale) {
+                errors.push(ValidationError::new(
+                    format!("game_modes.{}.scale", GameModeFilters::field_name(name)),
+                    "must be between 0 and 1000",
+                ));
+            }
+        }
+
+        if self.recording.directory.trim().is_empty() {
+            errors.push(ValidationError::new(
+                "recording.directory",
+                "must not be empty",
+            ));
+        }
+
+        if self.recording.max_file_size_mb < 1 {
+            errors.push(ValidationError::new(
+                "recording.max_file_size_mb",
+                "must be at least 1",
+            ));
+        }
+
+        if self.recording.max_total_size_mb < self.recording.max_file_size_mb {
+            errors.push(ValidationError::new(
+                "recording.size",
+                "total must be at least the file size",
+            ));
+        }
+
+        errors
+    }
+
+    /// Reset every invalid setting to its default, dropping invalid weapon
+    /// modifiers, and keep the rest. Returns what was wrong.
+    pub fn reset_invalid(&mut self) -> Vec<ValidationError> {
+        let errors = self.validate();
+
+        let invalid_modifiers = errors
+            .iter()
+            .filter_map(|error| {
+                let rest = error.field.strip_prefix("weapon_modifiers.")?;
+                rest.split('.').next()?.parse::<usize>().ok()
+            })
+            .collect::<Vec<_>>();
+        let mut i = 0;
+        self.weapon_modifiers.retain(|_| {
+            i += 1;
+            !invalid_modifiers.contains(&(i - 1))
+        });
+
+        for error in &errors {
+            self.reset_field(&error.field);
+        }
+
+        // Resetting one end of a range can leave it out of order with the
+        // other end, which then resets both
+        let remaining = self.validate();
+        for error in &remaining {
+            self.reset_field(&error.field);
+        }
+
+        errors.into_iter().chain(remaining).collect()
+    }
+
+    fn reset_field(&mut self, field: &str) {
+        let default = Config::default();
+
+        match field {
+            "min_duration" => self.min_duration = default.min_duration,
+            "max_duration" => self.max_duration = default.max_duration,
+            "duration" => {
+                self.min_duration = default.min_duration;
+                self.max_duration = default.max_duration;
+            }
+            "min_intensity" => self.min_intensity = default.min_intensity,
+            "max_intensity" => self.max_intensity = default.max_intensity,
+            "intensity" => {
+                self.min_intensity = default.min_intensity;
+                self.max_intensity = default.max_intensity;
+            }
+            "follow" => self.follow = default.follow,
+            "expensive_death.min_equip_value" | "expensive_death.equip_value" => {
+                self.expensive_death.min_equip_value = default.expensive_death.min_equip_value;
+                self.expensive_death.max_equip_value = default.expensive_death.max_equip_value;
+            }
+            "low_money.threshold" => self.low_money.threshold = default.low_money.threshold,
+            "low_money.intensity" => self.low_money.intensity = default.low_money.intensity,
+            "low_money.duration" => self.low_money.duration = default.low_money.duration,
+            "recording.directory" => self.recording.directory = default.recording.directory,
+            "recording.max_file_size_mb" | "recording.size" => {
+                self.recording.max_file_size_mb = default.recording.max_file_size_mb;
+                self.recording.max_total_size_mb = default.recording.max_total_size_mb;
+            }
+            _ => {
+                if let Some(field_name) = field
+                    .strip_prefix("game_modes.")
+                    .and_then(|rest| rest.strip_suffix(".scale"))
+                {
+                    let defaults = default.game_modes.iter();
+                    for ((name, filter), (_, default)) in
+                        self.game_modes.iter_mut().into_iter().zip(defaults)
+                    {
+                        if GameModeFilters::field_name(name) == field_name {
+                            filter.scale = default.scale;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// First weapon modifier matching the weapon held at death, if any.
+    pub fn weapon_modifier(&self, weapon: &Weapon, equip_value: i32) -> Option<&WeaponModifier> {
+        self.weapon_modifiers
+            .iter()
+            .find(|modifier| modifier.matches(weapon, equip_value))
+    }
+
+    /// Save the config along with its secrets. The previous file is kept as
+    /// `<path>.bak` and the new one is swapped in atomically, so a failed save
+    /// never leaves a half-written config behind.
+    pub fn write_to_file(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+
+        if path.exists() {
+            let mut backup = path.as_os_str().to_owned();
+            backup.push(".bak");
+            // Configs from before secrets.json still hold credentials
+            fs::copy(path, &backup)
+                .and_then(|_| secrets::restrict(Path::new(&backup)))
+                .map_err(|e| {
+                    format!(
+                        "Failed to back up config to {}: {}",
+                        Path::new(&backup).display(),
+                        e
+                    )
+                })?;
+        }
+
+        paths::write_atomic(path, json.as_bytes(), false)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+        secrets::save(path, self)
+    }
+
+    /// Scale a punishment by a percentage. The max intensity and duration are
+    /// a hard ceiling no modifier can push past, and never exceed what PiShock
+    /// accepts.
+    pub fn scale_punishment(&self, intensity: i32, duration: i32, scale: i32) -> (i32, i32) {
+        (
+            (intensity * scale / 100).clamp(0, self.max_intensity.clamp(0, 100)),
+            (duration * scale / 100).clamp(1, self.max_duration.clamp(1, 15)),
+        )
+    }
+
+    /// Take the secrets of `other` for any that are empty here, for configs
+    /// from places that never see the secrets.
+    pub fn fill_secrets(&mut self, other: &Config) {
+        for (secret, other) in [
+            (&mut self.username, &other.username),
+            (&mut self.code, &other.code),
+            (&mut self.apikey, &other.apikey),
+            (&mut self.api_token, &other.api_token),
+        ] {
+            if secret.is_empty() {
+                *secret = other.clone();
+            }
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use serde_json::json;
+
+    use super::*;
+
+    /// An empty directory for a test to put a config in.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("cs2shock-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn migrate_without_version_is_version_1() {
+        let mut value = json!({ "min_intensity": 20 });
+
+        assert_eq!(migrate(&mut value), Ok(1));
+        assert_eq!(value["version"], json!(CONFIG_VERSION));
+        assert_eq!(value["min_intensity"], json!(20));
+    }
+
+    #[test]
+    fn migrate_treats_version_0_as_1() {
+        let mut value = json!({ "version": 0 });
+
+        assert_eq!(migrate(&mut value), Ok(1));
+        assert_eq!(value["version"], json!(CONFIG_VERSION));
+    }
+
+    #[test]
+    fn migrate_keeps_current_configs() {
+        let mut value = json!({ "version": CONFIG_VERSION, "max_duration": 5 });
+        let original = value.clone();
+
+        assert_eq!(migrate(&mut value), Ok(CONFIG_VERSION));
+        assert_eq!(value, original);
+    }
+
+    #[test]
+    fn migrate_loads_newer_versions() {
+        let mut value = json!({ "version": CONFIG_VERSION + 5, "max_duration": 5 });
+
+        assert_eq!(migrate(&mut value), Ok(CONFIG_VERSION + 5));
+        assert_eq!(value["max_duration"], json!(5));
+        let config = serde_json::from_value::<Config>(value).unwrap();
+        assert_eq!(config.max_duration, 5);
+    }
+
+    #[test]
+    fn migrate_rejects_non_objects() {
+        assert!(migrate(&mut json!([1, 2, 3])).is_err());
+        assert!(migrate(&mut json!("config")).is_err());
+    }
+
+    #[test]
+    fn every_older_version_has_a_migration() {
+        assert_eq!(MIGRATIONS.len(), CONFIG_VERSION as usize - 1);
+    }
+
+    #[test]
+    fn load_migrates_and_moves_credentials() {
+        let dir = temp_dir("migrate");
+        let path = dir.join("config.json");
+        fs::write(
+            &path,
+            json!({ "username": "user", "apikey": "key", "max_intensity": 40 }).to_string(),
+        )
+        .unwrap();
+
+        let config = Config::load(&path).unwrap().unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.max_intensity, 40);
+        assert_eq!(*config.username, "user");
+        assert_eq!(*config.apikey, "key");
 
-        return true;
+        let backup = fs::read_to_string(dir.join("config.json.v1.bak")).unwrap();
+        assert!(backup.contains("\"apikey\""));
+        let rewritten = fs::read_to_string(&path).unwrap();
+        assert!(!rewritten.contains("\"apikey\""));
+        let secrets = fs::read_to_string(secrets::path(&path)).unwrap();
+        assert!(secrets.contains("\"key\""));
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn load_leaves_newer_versions_alone() {
+        let dir = temp_dir("newer");
+        let path = dir.join("config.json");
+        let raw = json!({ "version": CONFIG_VERSION + 1, "some_new_setting": true }).to_string();
+        fs::write(&path, &raw).unwrap();
+
+        assert!(Config::load(&path).unwrap().is_some());
+        assert_eq!(fs::read_to_string(&path).unwrap(), raw);
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn load_backs_up_unreadable_configs() {
+        let dir = temp_dir("invalid");
+        let path = dir.join("config.json");
+        fs::write(&path, "{ not json").unwrap();
+
+        assert!(Config::load(&path).is_err());
+        assert_eq!(
+            fs::read_to_string(dir.join("config.json.invalid.bak")).unwrap(),
+            "{ not json"
+        );
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    fn fields(errors: &[ValidationError]) -> Vec<&str> {
+        errors.iter().map(|error| error.field.as_str()).collect()
+    }
+
+    #[test]
+    fn reset_invalid_keeps_valid_configs() {
+        let mut config = Config {
+            min_intensity: 10,
+            max_intensity: 50,
+            ..Config::default()
+        };
+        let original = config.clone();
+
+        assert!(config.reset_invalid().is_empty());
+        assert_eq!(config, original);
     }
 
-    pub fn write_to_file(&self, path: &str) {
-        let mut file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(true)
-            .open(path)
-            .expect(format!("Failed to open config file, {}", path).as_str());
+    #[test]
+    fn reset_invalid_resets_only_invalid_fields() {
+        let mut config = Config {
+            min_duration: 2,
+            max_duration: 20,
+            min_intensity: 10,
+            max_intensity: 50,
+            ..Config::default()
+        };
+
+        let errors = config.reset_invalid();
+        assert_eq!(fields(&errors), ["max_duration", "duration"]);
+        // 2 is out of order with the default max of 1, so both are reset
+        assert_eq!(config.min_duration, Config::default().min_duration);
+        assert_eq!(config.max_duration, Config::default().max_duration);
+        assert_eq!(config.min_intensity, 10);
+        assert_eq!(config.max_intensity, 50);
+        assert!(config.validate().is_empty());
+    }
+
+    #[test]
+    fn reset_invalid_fixes_ranges_left_out_of_order() {
+        let mut config = Config {
+            min_intensity: 50,
+            max_intensity: 200,
+            ..Config::default()
+        };
+
+        let errors = config.reset_invalid();
+        assert_eq!(fields(&errors), ["max_intensity", "intensity"]);
+        assert_eq!(config.min_intensity, Config::default().min_intensity);
+        assert_eq!(config.max_intensity, Config::default().max_intensity);
+        assert!(config.validate().is_empty());
+    }
+
+    #[test]
+    fn reset_invalid_resets_out_of_order_ranges() {
+        let mut config = Config {
+            min_intensity: 60,
+            max_intensity: 40,
+            ..Config::default()
+        };
+
+        assert_eq!(fields(&config.reset_invalid()), ["intensity"]);
+        assert_eq!(config.min_intensity, Config::default().min_intensity);
+        assert_eq!(config.max_intensity, Config::default().max_intensity);
+    }
+
+    #[test]
+    fn reset_invalid_drops_invalid_weapon_modifiers() {
+        let modifier = |pattern: &str, scale| WeaponModifier {
+            pattern: pattern.to_owned(),
+            scale,
+            max_equip_value: None,
+        };
+        let mut config = Config {
+            weapon_modifiers: vec![
+                modifier("awp", 200),
+                modifier(" ", 100),
+                modifier("knife", 5000),
+                modifier("pistol", 50),
+            ],
+            ..Config::default()
+        };
+
+        let errors = config.reset_invalid();
+        assert_eq!(
+            fields(&errors),
+            ["weapon_modifiers.1.pattern", "weapon_modifiers.2.scale"]
+        );
+        assert_eq!(
+            config.weapon_modifiers,
+            [modifier("awp", 200), modifier("pistol", 50)]
+        );
+    }
+
+    #[test]
+    fn reset_invalid_resets_game_mode_scales() {
+        let mut config = Config::default();
+        config.game_modes.arms_race.scale = -1;
+        config.game_modes.competitive.scale = 300;
+
+        assert_eq!(
+            fields(&config.reset_invalid()),
+            ["game_modes.arms_race.scale"]
+        );
+        assert_eq!(
+            config.game_modes.arms_race.scale,
+            GameModeFilters::default().arms_race.scale
+        );
+        assert_eq!(config.game_modes.competitive.scale, 300);
+    }
 
-        let json = serde_json::to_string_pretty(self).expect("Failed to serialize config");
+    #[test]
+    fn reset_invalid_resets_invalid_follow() {
+        let mut config = Config {
+            follow: FollowMode::Player("not a steamid".to_owned()),
+            ..Config::default()
+        };
 
-        file.write_all(json.as_bytes())
-            .expect("Failed to write config file");
+        assert_eq!(fields(&config.reset_invalid()), ["follow"]);
+        assert_eq!(config.follow, FollowMode::Own);
     }
 }
@@ -0,0 +1,252 @@
+use std::sync::atomic::Ordering;
+
+use axum::{
+    extract::{Request, State},
+    http::{header::AUTHORIZATION, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+    Json, Router,
+};
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    config::Config,
+    feed::{ActionOutcome, ActionTrigger},
+    pishock::PiShockOp,
+    profiles, AppState, GameState,
+};
+
+/// Control API for scripting a running instance, nested under `/api`.
+///
+/// Every route requires `Authorization: Bearer <api_token>`, and the whole
+/// API is disabled while `api_token` is empty.
+///
+/// - `GET /state` current game state and whether actions are paused
+/// - `GET /config`, `PUT /config` read or replace the config, an invalid
+///   config is rejected with every problem listed under `fields`
+/// - `GET /profiles` the active profile and every saved one
+/// - `PUT /profile` switch profile with `{ "name": "stream" }`, an empty name
+///   switches back to the plain config
+/// - `POST /pause`, `POST /resume` block or allow actions caused by game events
+/// - `POST /beep`, `POST /vibrate`, `POST /shock` send a manual action, with a
+///   body of `{ "intensity": 0-100, "duration": 1-15 }`
+pub fn router(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/state", get(get_state))
+        .route("/config", get(get_config).put(put_config))
+        .route("/profiles", get(get_profiles))
+        .route("/profile", put(put_profile))
+        .route("/pause", post(pause))
+        .route("/resume", post(resume))
+        .route("/beep", post(beep))
+        .route("/vibrate", post(vibrate))
+        .route("/shock", post(shock))
+        .route_layer(middleware::from_fn_with_state(state, authorize))
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct StateResponse {
+    pub paused: bool,
+    pub game: GameState,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ManualAction {
+    #[serde(default)]
+    pub intensity: i32,
+    pub duration: i32,
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({ "error": message }))).into_response()
+}
+
+async fn authorize(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let token = state.config.read().await.api_token.clone();
+    if token.is_empty() {
+        return error(
+            StatusCode::FORBIDDEN,
+            "Control API is disabled, set api_token to enable it",
+        );
+    }
+
+    let provided = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    if provided != Some(token.as_str()) {
+        return error(StatusCode::UNAUTHORIZED, "Missing or invalid token");
+    }
+
+    next.run(request).await
+}
+
+async fn get_state(State(state): State<AppState>) -> Json<StateResponse> {
+    Json(StateResponse {
+        paused: state.paused.load(Ordering::Relaxed),
+        game: state.game_state.lock().await.clone(),
+    })
+}
+
+async fn get_config(State(state): State<AppState>) -> Json<Config> {
+    Json(state.config.read().await.clone())
+}
+
+async fn put_config(State(state): State<AppState>, Json(mut config): Json<Config>) -> Response {
+    let errors = config.validate();
+    if !errors.is_empty() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(json!({ "error": "Invalid config", "fields": errors })),
+        )
+            .into_response();
+    }
+
+    let mut current = state.config.write().await;
+    // The caps are the user's safety net, only they get to raise them
+    if config.max_intensity > current.max_intensity || config.max_duration > current.max_duration {
+        return error(
+            StatusCode::FORBIDDEN,
+            "max_intensity and max_duration can only be raised in the settings window or config file",
+        );
+    }
+    config.profile = current.profile.clone();
+    // Secrets are left out of `GET /config`, keep them unless new ones are sent
+    config.fill_secrets(&current);
+    *current = config;
+    state.config_revision.fetch_add(1, Ordering::Relaxed);
+    if let Some(path) = &state.config_path {
+        if let Err(e) = profiles::save(path, &current) {
+            return error(StatusCode::INTERNAL_SERVER_ERROR, &e);
+        }
+    }
+    info!(target: "Control API", "Config updated");
+
+    Json(current.clone()).into_response()
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ProfilesResponse {
+    pub active: String,
+    pub profiles: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SwitchProfile {
+    #[serde(default)]
+    pub name: String,
+}
+
+async fn get_profiles(State(state): State<AppState>) -> Json<ProfilesResponse> {
+    Json(ProfilesResponse {
+        active: state.config.read().await.profile.clone(),
+        profiles: state
+            .config_path
+            .as_deref()
+            .map(profiles::list)
+            .unwrap_or_default(),
+    })
+}
+
+async fn put_profile(State(state): State<AppState>, Json(switch): Json<SwitchProfile>) -> Response {
+    match profiles::switch(&state, &switch.name).await {
+        Ok(config) => {
+            info!(target: "Control API", "Switched to profile {:?}", switch.name);
+            Json(config).into_response()
+        }
+        Err(e) => error(StatusCode::BAD_REQUEST, &e),
+    }
+}
+
+async fn pause(State(state): State<AppState>) -> Json<serde_json::Value> {
+    state.paused.store(true, Ordering::Relaxed);
+    info!(target: "Control API", "Paused");
+    Json(json!({ "paused": true }))
+}
+
+async fn resume(State(state): State<AppState>) -> Json<serde_json::Value> {
+    state.paused.store(false, Ordering::Relaxed);
+    info!(target: "Control API", "Resumed");
+    Json(json!({ "paused": false }))
+}
+
+async fn beep(State(state): State<AppState>, Json(action): Json<ManualAction>) -> Response {
+    send(
+        state,
+        action.duration,
+        PiShockOp::Beep {
+            duration: action.duration,
+        },
+    )
+    .await
+}
+
+async fn vibrate(State(state): State<AppState>, Json(action): Json<ManualAction>) -> Response {
+    if let Err(e) = within_limits(&state, &action).await {
+        return error(StatusCode::BAD_REQUEST, &e);
+    }
+
+    send(
+        state,
+        action.duration,
+        PiShockOp::Vibrate {
+            intensity: action.intensity,
+            duration: action.duration,
+        },
+    )
+    .await
+}
+
+async fn shock(State(state): State<AppState>, Json(action): Json<ManualAction>) -> Response {
+    if let Err(e) = within_limits(&state, &action).await {
+        return error(StatusCode::BAD_REQUEST, &e);
+    }
+
+    send(
+        state,
+        action.duration,
+        PiShockOp::Shock {
+            intensity: action.intensity,
+            duration: action.duration,
+        },
+    )
+    .await
+}
+
+/// Manual actions are held to the same caps as the ones caused by the game,
+/// whoever holds the token cannot go past what the user configured.
+async fn within_limits(state: &AppState, action: &ManualAction) -> Result<(), String> {
+    let config = state.config.read().await;
+
+    if !(0..=config.max_intensity).contains(&action.intensity) {
+        return Err(format!(
+            "intensity must be between 0 and {} (max_intensity)",
+            config.max_intensity
+        ));
+    }
+    if action.duration > config.max_duration {
+        return Err(format!(
+            "duration must be at most {} (max_duration)",
+            config.max_duration
+        ));
+    }
+
+    Ok(())
+}
+
+async fn send(state: AppState, duration: i32, op: PiShockOp) -> Response {
+    if !(1..=15).contains(&duration) {
+        return error(StatusCode::BAD_REQUEST, "duration must be between 1 and 15");
+    }
+
+    info!(target: "Control API", "Manual {:?}", op);
+    match state.perform(op, ActionTrigger::Manual).await {
+        ActionOutcome::Failed { error: e } => error(StatusCode::BAD_GATEWAY, &e),
+        _ => Json(json!({ "ok": true })).into_response(),
+    }
+}
@@ -0,0 +1,551 @@
+use std::fmt;
+
+use log::{debug, info};
+use serde::Serialize;
+
+use crate::{
+    config::FollowMode,
+    gamestateintegration::{GameMode, MapPhase, Payload, RoundPhase, Weapon},
+    lifecycle::MatchTransition,
+    GameState, PlayerState,
+};
+
+/// Something that happened in game, detected by comparing a payload to the
+/// previously tracked [`GameState`].
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GameEvent {
+    /// Joined a map, either fresh, mid-match or after a reconnect
+    MatchJoined { map: String, mode: GameMode },
+    /// Warmup or a previous match ended and a new one went live
+    MatchStarted,
+    /// The match reached game over
+    MatchEnded,
+    /// Left the map, back in the main menu
+    MatchLeft,
+    /// Freezetime of a live round began, with the followed player's money
+    FreezetimeStarted {
+        money: i32,
+        /// First round of a half, where everyone starts with the same money
+        pistol_round: bool,
+    },
+    /// Freezetime ended
+    RoundStarted,
+    /// The followed player took damage and survived
+    Damaged { amount: i32, health: i32 },
+    /// The followed player died
+    Died {
+        /// Health before the killing blow
+        last_health: i32,
+        /// Value of the loadout that was lost
+        equip_value: i32,
+        /// Weapon in hand at the time of death
+        weapon: Option<Weapon>,
+    },
+}
+
+impl GameEvent {
+    /// Short machine readable name, matching the serialized `type`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            GameEvent::MatchJoined { .. } => "match_joined",
+            GameEvent::MatchStarted => "match_started",
+            GameEvent::MatchEnded => "match_ended",
+            GameEvent::MatchLeft => "match_left",
+            GameEvent::FreezetimeStarted { .. } => "freezetime_started",
+            GameEvent::RoundStarted => "round_started",
+            GameEvent::Damaged { .. } => "damaged",
+            GameEvent::Died { .. } => "died",
+        }
+    }
+}
+
+impl fmt::Display for GameEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameEvent::MatchJoined { map, mode } => write!(f, "Joined {:?} on {}", mode, map),
+            GameEvent::MatchStarted => write!(f, "Match started"),
+            GameEvent::MatchEnded => write!(f, "Match ended"),
+            GameEvent::MatchLeft => write!(f, "Left match"),
+            GameEvent::FreezetimeStarted {
+                money,
+                pistol_round,
+            } => {
+                write!(f, "Freezetime started with {} money", money)?;
+                if *pistol_round {
+                    write!(f, ", pistol round")?;
+                }
+                Ok(())
+            }
+            GameEvent::RoundStarted => write!(f, "Round started"),
+            GameEvent::Damaged { amount, health } => {
+                write!(f, "Took {} damage, {} health left", amount, health)
+            }
+            GameEvent::Died {
+                last_health,
+                equip_value,
+                weapon,
+            } => {
+                write!(
+                    f,
+                    "Died with {} health and {} equip value",
+                    last_health, equip_value
+                )?;
+                if let Some(weapon) = weapon {
+                    write!(f, " holding {}", weapon.name)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl GameState {
+    /// Track a payload and return the events it caused, in order.
+    pub fn update(&mut self, payload: &Payload, follow: &FollowMode) -> Vec<GameEvent> {
+        let mut events = Vec::new();
+
+        match self.update_match(payload.provider.as_ref(), payload.map.as_ref()) {
+            MatchTransition::Joined => {
+                if let Some(map) = &payload.map {
+                    events.push(GameEvent::MatchJoined {
+                        map: map.name.clone(),
+                        mode: map.mode.clone(),
+                    });
+                }
+            }
+            MatchTransition::Started => events.push(GameEvent::MatchStarted),
+            MatchTransition::Ended => events.push(GameEvent::MatchEnded),
+            MatchTransition::Left => events.push(GameEvent::MatchLeft),
+            MatchTransition::None => {}
+        }
+
+        let player = payload
+            .player
+            .as_ref()
+            .filter(|player| follow.follows(&self.steam_id, &player.steamid));
+
+        if let Some(round) = &payload.round {
+            if self.round_phase == RoundPhase::Freezetime && round.phase == RoundPhase::Live {
+                events.push(GameEvent::RoundStarted);
+            }
+
+            if self.round_phase != RoundPhase::Freezetime
+                && round.phase == RoundPhase::Freezetime
+                && self.map_phase == MapPhase::Live
+            {
+                let pistol_round =
+                    self.halftime || payload.map.as_ref().is_some_and(|map| map.round == 0);
+                self.halftime = false;
+
+                if let Some(state) = player.and_then(|player| player.state) {
+                    events.push(GameEvent::FreezetimeStarted {
+                        money: state.money,
+                        pistol_round,
+                    });
+                }
+            }
+
+            self.round_phase = round.phase.clone();
+        }
+
+        if self.map_phase != MapPhase::Live {
+            return events;
+        }
+
+        if payload.player.is_none() {
+            return events;
+        }
+
+        let Some(player) = player else {
+            // Someone else is on screen, anything tracked is stale by the time they switch back
+            if self.player_state.take().is_some() {
+                debug!("No longer observing followed player");
+            }
+            return events;
+        };
+        let (Some(state), Some(stats)) = (player.state, player.match_stats) else {
+            return events;
+        };
+
+        if self
+            .player_state
+            .as_ref()
+            .is_some_and(|player_state| player_state.steamid != player.steamid)
+        {
+            info!("Now following {}", player.name);
+            self.player_state = None;
+        }
+
+        if let Some(player_state) = &mut self.player_state {
+            if player_state.health > state.health && state.health > 0 {
+                events.push(GameEvent::Damaged {
+                    amount: player_state.health - state.health,
+                    health: state.health,
+                });
+            }
+
+            if stats.deaths > player_state.deaths {
+                events.push(GameEvent::Died {
+                    last_health: player_state.health,
+                    equip_value: player_state.equip_value,
+                    weapon: player_state.active_weapon.clone(),
+                });
+            }
+
+            player_state.name = player.name.clone();
+            player_state.health = state.health;
+            player_state.armor = state.armor;
+            player_state.kills = stats.kills;
+            player_state.deaths = stats.deaths;
+            player_state.money = state.money;
+            player_state.equip_value = state.equip_value;
+            player_state.active_weapon = player.active_weapon().cloned();
+        } else {
+            debug!("Player state initialized");
+
+            self.player_state = Some(PlayerState {
+                steamid: player.steamid.clone(),
+                name: player.name.clone(),
+                health: state.health,
+                armor: state.armor,
+                kills: stats.kills,
+                deaths: stats.deaths,
+                money: state.money,
+                equip_value: state.equip_value,
+                active_weapon: player.active_weapon().cloned(),
+            });
+        }
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    const OWN: &str = "76561198000000000";
+    const OTHER: &str = "76561198000000001";
+
+    /// The `player` block of `steamid` with `health`, `deaths` and an AK in
+    /// hand.
+    fn player(steamid: &str, health: i32, deaths: i32) -> Value {
+        json!({
+            "steamid": steamid,
+            "name": steamid,
+            "state": {
+                "health": health, "armor": 100, "helmet": true, "flashed": 0,
+                "smoked": 0, "burning": 0, "money": 1500, "round_kills": 0,
+                "round_killhs": 0, "equip_value": 3700,
+            },
+            "match_stats": { "kills": 0, "assists": 0, "deaths": deaths, "mvps": 0, "score": 0 },
+            "weapons": {
+                "weapon_0": { "name": "weapon_knife", "type": "Knife", "state": "holstered" },
+                "weapon_1": { "name": "weapon_ak47", "type": "Rifle", "state": "active" },
+            },
+        })
+    }
+
+    /// A payload sent by our own client on `de_dust2`.
+    fn payload(map_phase: &str, round_phase: &str, player: Value) -> Payload {
+        serde_json::from_value(json!({
+            "provider": {
+                "name": "Counter-Strike: Global Offensive",
+                "appid": 730,
+                "version": 14000,
+                "steamid": OWN,
+                "timestamp": 1000,
+            },
+            "map": { "mode": "competitive", "name": "de_dust2", "phase": map_phase, "round": 3 },
+            "round": { "phase": round_phase },
+            "player": player,
+        }))
+        .unwrap()
+    }
+
+    /// A game state in a live round, tracking `player(OWN, 100, 0)`.
+    fn live(follow: &FollowMode) -> GameState {
+        let mut state = GameState::default();
+        state.update(&payload("live", "live", player(OWN, 100, 0)), follow);
+        state
+    }
+
+    #[test]
+    fn first_payload_only_starts_tracking() {
+        let mut state = GameState::default();
+        let events = state.update(
+            &payload("live", "live", player(OWN, 100, 0)),
+            &FollowMode::Own,
+        );
+
+        assert!(
+            matches!(events[..], [GameEvent::MatchJoined { .. }]),
+            "{:?}",
+            events
+        );
+        let player_state = state.player_state.unwrap();
+        assert_eq!(player_state.health, 100);
+        assert_eq!(player_state.equip_value, 3700);
+        assert_eq!(
+            player_state.active_weapon.map(|weapon| weapon.name),
+            Some("weapon_ak47".to_owned())
+        );
+    }
+
+    #[test]
+    fn damage_is_detected() {
+        let mut state = live(&FollowMode::Own);
+        let events = state.update(
+            &payload("live", "live", player(OWN, 73, 0)),
+            &FollowMode::Own,
+        );
+
+        assert!(
+            matches!(
+                events[..],
+                [GameEvent::Damaged {
+                    amount: 27,
+                    health: 73
+                }]
+            ),
+            "{:?}",
+            events
+        );
+    }
+
+    #[test]
+    fn death_reports_the_state_before_it() {
+        let mut state = live(&FollowMode::Own);
+        state.update(
+            &payload("live", "live", player(OWN, 40, 0)),
+            &FollowMode::Own,
+        );
+        let events = state.update(
+            &payload("live", "live", player(OWN, 0, 1)),
+            &FollowMode::Own,
+        );
+
+        // Dying is not damage as well
+        let [GameEvent::Died {
+            last_health,
+            equip_value,
+            weapon,
+        }] = &events[..]
+        else {
+            panic!("{:?}", events);
+        };
+        assert_eq!(*last_health, 40);
+        assert_eq!(*equip_value, 3700);
+        assert_eq!(
+            weapon.as_ref().map(|weapon| weapon.name.as_str()),
+            Some("weapon_ak47")
+        );
+        assert_eq!(state.player_state.map(|player| player.deaths), Some(1));
+    }
+
+    #[test]
+    fn nothing_is_tracked_outside_live_matches() {
+        let mut state = GameState::default();
+        state.update(
+            &payload("warmup", "live", player(OWN, 100, 0)),
+            &FollowMode::Own,
+        );
+        let events = state.update(
+            &payload("warmup", "live", player(OWN, 0, 1)),
+            &FollowMode::Own,
+        );
+
+        assert!(events.is_empty(), "{:?}", events);
+        assert!(state.player_state.is_none());
+    }
+
+    #[test]
+    fn own_ignores_other_players() {
+        let mut state = live(&FollowMode::Own);
+        let events = state.update(
+            &payload("live", "live", player(OTHER, 0, 5)),
+            &FollowMode::Own,
+        );
+
+        assert!(events.is_empty(), "{:?}", events);
+        // Stale by the time we are back on our own player
+        assert!(state.player_state.is_none());
+    }
+
+    #[test]
+    fn observed_switching_players_starts_over() {
+        let follow = FollowMode::Observed;
+        let mut state = live(&follow);
+        let events = state.update(&payload("live", "live", player(OTHER, 20, 5)), &follow);
+
+        assert!(events.is_empty(), "{:?}", events);
+        let player_state = state.player_state.as_ref().unwrap();
+        assert_eq!(player_state.steamid, OTHER);
+        assert_eq!(player_state.deaths, 5);
+
+        let events = state.update(&payload("live", "live", player(OTHER, 0, 6)), &follow);
+        assert!(
+            matches!(
+                events[..],
+                [GameEvent::Died {
+                    last_health: 20,
+                    ..
+                }]
+            ),
+            "{:?}",
+            events
+        );
+    }
+
+    #[test]
+    fn player_follows_only_that_player() {
+        let follow = FollowMode::Player(format!(" {} ", OTHER));
+        let mut state = live(&follow);
+        assert!(state.player_state.is_none());
+
+        state.update(&payload("live", "live", player(OTHER, 100, 0)), &follow);
+        let events = state.update(&payload("live", "live", player(OTHER, 90, 0)), &follow);
+        assert!(
+            matches!(events[..], [GameEvent::Damaged { amount: 10, .. }]),
+            "{:?}",
+            events
+        );
+    }
+
+    #[test]
+    fn round_start_follows_freezetime() {
+        let mut state = GameState::default();
+        state.update(
+            &payload("live", "freezetime", player(OWN, 100, 0)),
+            &FollowMode::Own,
+        );
+        let events = state.update(
+            &payload("live", "live", player(OWN, 100, 0)),
+            &FollowMode::Own,
+        );
+
+        assert!(
+            matches!(events[..], [GameEvent::RoundStarted]),
+            "{:?}",
+            events
+        );
+    }
+
+    #[test]
+    fn menu_players_without_state_are_skipped() {
+        let mut state = live(&FollowMode::Own);
+        let events = state.update(
+            &payload(
+                "live",
+                "live",
+                json!({ "steamid": OWN, "name": OWN, "activity": "menu" }),
+            ),
+            &FollowMode::Own,
+        );
+
+        assert!(events.is_empty(), "{:?}", events);
+        assert_eq!(state.player_state.map(|player| player.health), Some(100));
+    }
+
+    /// Freezetime of round `round`, after `state` was in a live round.
+    fn freezetime(state: &mut GameState, round: i32) -> Vec<GameEvent> {
+        let mut next = payload("live", "freezetime", player(OWN, 100, 0));
+        if let Some(map) = &mut next.map {
+            map.round = round;
+        }
+        state.update(&next, &FollowMode::Own)
+    }
+
+    #[test]
+    fn freezetime_reports_money() {
+        let mut state = live(&FollowMode::Own);
+        let events = freezetime(&mut state, 3);
+
+        assert!(
+            matches!(
+                events[..],
+                [GameEvent::FreezetimeStarted {
+                    money: 1500,
+                    pistol_round: false
+                }]
+            ),
+            "{:?}",
+            events
+        );
+    }
+
+    #[test]
+    fn first_round_is_a_pistol_round() {
+        let mut state = live(&FollowMode::Own);
+        let events = freezetime(&mut state, 0);
+
+        assert!(
+            matches!(
+                events[..],
+                [GameEvent::FreezetimeStarted {
+                    pistol_round: true,
+                    ..
+                }]
+            ),
+            "{:?}",
+            events
+        );
+    }
+
+    #[test]
+    fn first_round_after_halftime_is_a_pistol_round() {
+        let mut state = live(&FollowMode::Own);
+        state.update(
+            &payload("intermission", "over", player(OWN, 100, 0)),
+            &FollowMode::Own,
+        );
+        state.update(
+            &payload("live", "live", player(OWN, 100, 0)),
+            &FollowMode::Own,
+        );
+
+        let events = freezetime(&mut state, 12);
+        assert!(
+            matches!(
+                events[..],
+                [GameEvent::FreezetimeStarted {
+                    pistol_round: true,
+                    ..
+                }]
+            ),
+            "{:?}",
+            events
+        );
+
+        state.update(
+            &payload("live", "live", player(OWN, 100, 0)),
+            &FollowMode::Own,
+        );
+        let events = freezetime(&mut state, 13);
+        assert!(
+            matches!(
+                events[..],
+                [GameEvent::FreezetimeStarted {
+                    pistol_round: false,
+                    ..
+                }]
+            ),
+            "{:?}",
+            events
+        );
+    }
+
+    #[test]
+    fn no_freezetime_money_for_other_players() {
+        let mut state = live(&FollowMode::Own);
+        let mut next = payload("live", "freezetime", player(OTHER, 100, 0));
+        if let Some(map) = &mut next.map {
+            map.round = 3;
+        }
+
+        assert!(state.update(&next, &FollowMode::Own).is_empty());
+    }
+}
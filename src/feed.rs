@@ -0,0 +1,209 @@
+//! Live feed of game events and device actions, streamed as Server-Sent
+//! Events from `GET /events`.
+//!
+//! Every message is a JSON object with a `kind` and a `time` in
+//! milliseconds since the unix epoch, the SSE event name matches `kind`.
+//!
+//! ```json
+//! { "kind": "event", "time": 1700000000000,
+//!   "event": { "type": "died", "last_health": 40, "equip_value": 4700, "weapon": null } }
+//!
+//! { "kind": "action", "time": 1700000000000, "trigger": { "event": "died" },
+//!   "op": { "Shock": { "intensity": 45, "duration": 3 } },
+//!   "outcome": { "status": "executed" } }
+//! ```
+//!
+//! `event` is a serialized [`GameEvent`], its `type` is one of
+//! [`GameEvent::kind`]. `trigger` is `"manual"` for actions sent through the
+//! control API or GUI. `outcome.status` is `executed`, `failed` with an
+//! `error`, or `blocked` with a `reason`.
+
+use std::{
+    collections::VecDeque,
+    convert::Infallible,
+    sync::atomic::Ordering,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    extract::State,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use log::info;
+use serde::Serialize;
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
+
+use crate::{
+    backend::Backend,
+    events::GameEvent,
+    pishock::{Credentials, PiShockOp},
+    AppState,
+};
+
+/// Messages kept for slow subscribers before they start missing some.
+pub const FEED_CAPACITY: usize = 256;
+
+/// Actions kept in the [`History`], older ones are dropped.
+pub const HISTORY_CAPACITY: usize = 200;
+
+/// Milliseconds since the unix epoch.
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// What caused an action.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionTrigger {
+    /// A game event, by [`GameEvent::kind`]
+    Event(String),
+    /// The control API or GUI
+    Manual,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ActionOutcome {
+    Executed,
+    Failed { error: String },
+    Blocked { reason: String },
+}
+
+impl ActionOutcome {
+    pub fn status(&self) -> &'static str {
+        match self {
+            ActionOutcome::Executed => "executed",
+            ActionOutcome::Failed { .. } => "failed",
+            ActionOutcome::Blocked { .. } => "blocked",
+        }
+    }
+}
+
+/// A device action and what became of it.
+#[derive(Serialize, Debug, Clone)]
+pub struct ActionRecord {
+    pub time: u64,
+    pub trigger: ActionTrigger,
+    pub op: PiShockOp,
+    pub outcome: ActionOutcome,
+}
+
+/// The most recent actions, oldest first.
+#[derive(Debug, Default)]
+pub struct History {
+    records: VecDeque<ActionRecord>,
+}
+
+impl History {
+    pub fn push(&mut self, record: ActionRecord) {
+        if self.records.len() == HISTORY_CAPACITY {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &ActionRecord> {
+        self.records.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FeedMessage {
+    Event { time: u64, event: GameEvent },
+    Action(ActionRecord),
+}
+
+impl FeedMessage {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            FeedMessage::Event { .. } => "event",
+            FeedMessage::Action(_) => "action",
+        }
+    }
+}
+
+impl AppState {
+    /// Publish a detected game event to the feed.
+    pub fn publish_event(&self, event: GameEvent) {
+        // Sending only fails when nobody is subscribed
+        let _ = self.feed.send(FeedMessage::Event {
+            time: now_millis(),
+            event,
+        });
+    }
+
+    /// Send an action through the backend and publish the outcome, actions
+    /// caused by game events are blocked while paused.
+    pub async fn perform(&self, op: PiShockOp, trigger: ActionTrigger) -> ActionOutcome {
+        self.metrics.action_queued();
+
+        let outcome = if trigger != ActionTrigger::Manual && self.paused.load(Ordering::Relaxed) {
+            info!("Paused, blocked {:?}", op);
+            ActionOutcome::Blocked {
+                reason: "paused".to_owned(),
+            }
+        } else {
+            // Never hold the config lock across the request, a writer waiting
+            // behind it would block every other reader until it completes
+            let credentials = Credentials::from_config(&*self.config.read().await);
+            let started = Instant::now();
+            let result = self.backend.send(credentials, op.clone()).await;
+            if self.backend == Backend::PiShock {
+                self.metrics.pishock_latency(started.elapsed());
+            }
+            self.health.lock().await.backend_result(&result);
+
+            match result {
+                Ok(_) => ActionOutcome::Executed,
+                Err(error) => ActionOutcome::Failed { error },
+            }
+        };
+
+        self.metrics.action_finished(&op, &outcome);
+
+        let record = ActionRecord {
+            time: now_millis(),
+            trigger,
+            op,
+            outcome: outcome.clone(),
+        };
+        self.history.lock().await.push(record.clone());
+        let _ = self.feed.send(FeedMessage::Action(record));
+
+        outcome
+    }
+}
+
+/// `GET /events`, the feed as Server-Sent Events.
+pub async fn stream(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let stream = BroadcastStream::new(state.feed.subscribe())
+        // Lagging subscribers skip what they missed
+        .filter_map(|message| message.ok())
+        .filter_map(|message| {
+            Event::default()
+                .event(message.kind())
+                .json_data(&message)
+                .ok()
+        })
+        .map(Ok);
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
@@ -1,37 +1,64 @@
-use serde::Deserialize;
+use std::collections::HashMap;
 
-#[derive(Deserialize, Debug, Clone)]
+use serde::{Deserialize, Serialize};
+
+/// `map.mode`, modes without a dedicated variant are [`GameMode::Other`].
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GameMode {
+    Competitive,
+    Casual,
+    Deathmatch,
+    #[serde(rename = "scrimcomp2v2")]
+    Wingman,
+    #[serde(rename = "gungameprogressive")]
+    ArmsRace,
+    #[serde(other)]
+    Other,
+}
+
+/// The `map` component.
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Map {
-    pub mode: String,
+    pub mode: GameMode,
     pub name: String,
     pub phase: MapPhase,
+    /// Rounds played so far, 0 during the first round
+    #[serde(default)]
+    pub round: i32,
 }
 
-#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+/// `map.phase`, the phase of the whole match.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum MapPhase {
     Warmup,
     Intermission,
     GameOver,
     Live,
+    #[serde(other)]
     Unknown,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+/// The `round` component.
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Round {
     pub phase: RoundPhase,
 }
 
-#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+/// `round.phase`, the phase of the current round.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum RoundPhase {
     Freezetime,
     Live,
     Over,
+    #[serde(other)]
     Unknown,
 }
 
-#[derive(Deserialize, Debug, Clone, Copy)]
+/// The `player_state` component, state of the observed player this round.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
 pub struct PlayerState {
     pub health: i32,
     pub armor: i32,
@@ -45,7 +72,8 @@ pub struct PlayerState {
     pub equip_value: i32,
 }
 
-#[derive(Deserialize, Debug, Clone, Copy)]
+/// The `player_match_stats` component, totals for the whole match.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
 pub struct PlayerMatchStats {
     pub kills: i32,
     pub assists: i32,
@@ -54,24 +82,69 @@ pub struct PlayerMatchStats {
     pub score: i32,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+/// `state` of an entry in `player.weapons`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WeaponState {
+    Active,
+    Holstered,
+    Reloading,
+    #[serde(other)]
+    Unknown,
+}
+
+/// An entry in `player.weapons`, from the `player_weapons` component.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Weapon {
+    pub name: String,
+    /// Category such as `Knife`, `Pistol` or `SniperRifle`
+    #[serde(rename = "type", default)]
+    pub kind: String,
+    pub state: WeaponState,
+    pub ammo_clip: Option<i32>,
+    pub ammo_clip_max: Option<i32>,
+    pub ammo_reserve: Option<i32>,
+}
+
+/// The `player` block, the observed player which is not necessarily the
+/// client running CS2, see [`Provider::steamid`].
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Player {
     pub steamid: String,
     pub name: String,
-    pub state: PlayerState,
-    pub match_stats: PlayerMatchStats,
+    /// Missing outside of a match, e.g. in the main menu
+    pub state: Option<PlayerState>,
+    /// Missing outside of a match, e.g. in the main menu
+    pub match_stats: Option<PlayerMatchStats>,
+    #[serde(default)]
+    pub weapons: HashMap<String, Weapon>,
+}
+
+impl Player {
+    /// The weapon currently in hand, reloading counts as in hand.
+    pub fn active_weapon(&self) -> Option<&Weapon> {
+        self.weapons
+            .values()
+            .find(|weapon| matches!(weapon.state, WeaponState::Active | WeaponState::Reloading))
+    }
 }
 
-#[derive(Deserialize, Debug, Clone)]
+/// The `provider` component, describes the CS2 client sending the data.
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Provider {
     pub name: String,
     pub appid: i32,
     pub version: i32,
+    /// SteamID64 of the client, differs from [`Player::steamid`] while
+    /// spectating, coaching or casting
     pub steamid: String,
+    /// Unix timestamp in seconds
     pub timestamp: i32,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+/// A single GSI post, components are missing when not subscribed to or
+/// not applicable, e.g. `map` while in the main menu.
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Payload {
     pub provider: Option<Provider>,
     pub map: Option<Map>,
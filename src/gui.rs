@@ -1,19 +1,44 @@
-use std::{process, sync::Arc};
+use std::{
+    fs,
+    path::Path,
+    process,
+    sync::{atomic::Ordering, Arc, OnceLock},
+    time::Duration,
+};
 
 use eframe::icon_data::from_png_bytes;
-use egui::{widgets::DragValue, Button, Id, ViewportBuilder};
-use log::{debug, info};
-use tokio::sync::RwLock;
+use egui::{widgets::DragValue, Button, Color32, Id, ViewportBuilder};
+use log::{debug, error, info, warn};
+use rand::{distributions::Alphanumeric, Rng};
+use time::{macros::format_description, OffsetDateTime, UtcOffset};
+use tokio::sync::{watch, Mutex, RwLock};
 
 use crate::{
-    config::{Config, ShockMode},
-    pishock,
+    backend::Backend,
+    config::{
+        Config, FollowMode, GameModeFilters, ShockMode, TriggerOp, ValidationError, WeaponModifier,
+    },
+    feed::{ActionOutcome, ActionRecord, ActionTrigger},
+    pishock::PiShockOp,
+    presets::Preset,
+    profiles,
+    simulator::{Scenario, SimStep, Simulator},
+    AppState, GameState,
 };
 
-pub async fn run(config: Arc<RwLock<Config>>) {
+/// Offset of local time, `None` when it could not be determined.
+static LOCAL_OFFSET: OnceLock<Option<UtcOffset>> = OnceLock::new();
+
+/// Remember the local time offset for showing times. Call it before any
+/// other threads are started, on Linux it cannot be read after that.
+pub fn capture_local_offset() {
+    let _ = LOCAL_OFFSET.set(UtcOffset::current_local_offset().ok());
+}
+
+pub async fn run(state: AppState, port: u16) {
     let png_bytes = include_bytes!("../assets/icon.png");
     let viewport = ViewportBuilder::default()
-        .with_inner_size([320.0, 360.0])
+        .with_inner_size([320.0, 480.0])
         .with_resizable(false)
         .with_icon(Arc::new(
             from_png_bytes(png_bytes).expect("Failed to load icon"),
@@ -24,149 +49,629 @@ pub async fn run(config: Arc<RwLock<Config>>) {
         ..Default::default()
     };
 
-    let changes = config.read().await.clone();
+    let changes = state.config.read().await.clone();
+    let preset_file = state
+        .config_path
+        .as_deref()
+        .and_then(Path::parent)
+        .unwrap_or(Path::new(""))
+        .join("preset.json")
+        .display()
+        .to_string();
     let _ = eframe::run_native(
         "CS2 Shock",
         options,
-        Box::new(|_cc| Box::new(MyApp { config, changes })),
+        Box::new(move |cc| {
+            let mut game = state.game_updates.subscribe();
+            let ctx = cc.egui_ctx.clone();
+            tokio::spawn(async move {
+                while game.changed().await.is_ok() {
+                    ctx.request_repaint();
+                }
+            });
+
+            Box::new(MyApp {
+                game: state.game_updates.subscribe(),
+                history_filter: HistoryFilter::default(),
+                config: state.config.clone(),
+                revision: state.config_revision.load(Ordering::Relaxed),
+                state,
+                base: changes.clone(),
+                changes,
+                conflict: false,
+                new_profile: String::new(),
+                save_error: None,
+                preset_input: String::new(),
+                preset_file,
+                preset_status: None,
+                simulator: Arc::new(Mutex::new(Simulator::new(&format!(
+                    "http://127.0.0.1:{}/data",
+                    port
+                )))),
+            })
+        }),
     );
 }
 
 struct MyApp {
+    state: AppState,
     config: Arc<RwLock<Config>>,
     changes: Config,
+    /// The config `changes` started from, to tell edits apart from reloads
+    base: Config,
+    /// Last seen [`AppState::config_revision`]
+    revision: u64,
+    /// The config was replaced while there were unsaved edits
+    conflict: bool,
+    /// Name typed in for a new profile
+    new_profile: String,
+    /// Why the last save failed, if it did
+    save_error: Option<String>,
+    /// Pasted preset code or JSON, previewed before it is applied
+    preset_input: String,
+    preset_file: String,
+    /// Outcome of the last preset copy, export or import
+    preset_status: Option<Result<String, String>>,
+    simulator: Arc<Mutex<Simulator>>,
+    /// Game state as of the last payload
+    game: watch::Receiver<GameState>,
+    history_filter: HistoryFilter,
+}
+
+/// Which actions the history shows.
+struct HistoryFilter {
+    executed: bool,
+    failed: bool,
+    blocked: bool,
+    events: bool,
+    manual: bool,
+}
+
+impl Default for HistoryFilter {
+    fn default() -> Self {
+        Self {
+            executed: true,
+            failed: true,
+            blocked: true,
+            events: true,
+            manual: true,
+        }
+    }
+}
+
+impl HistoryFilter {
+    fn shows(&self, record: &ActionRecord) -> bool {
+        let outcome = match record.outcome {
+            ActionOutcome::Executed => self.executed,
+            ActionOutcome::Failed { .. } => self.failed,
+            ActionOutcome::Blocked { .. } => self.blocked,
+        };
+        let trigger = match record.trigger {
+            ActionTrigger::Event(_) => self.events,
+            ActionTrigger::Manual => self.manual,
+        };
+        outcome && trigger
+    }
 }
 
 impl eframe::App for MyApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.pick_up_reload();
+        let errors = self.changes.validate();
+
         egui::CentralPanel::default().show(ctx, |ui| {
-            ui.heading("CS2 Shock");
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                ui.heading("CS2 Shock");
+                self.status(ui);
+                egui::CollapsingHeader::new("Game")
+                    .default_open(true)
+                    .show(ui, |ui| self.game(ui));
+                ui.collapsing("History", |ui| self.history(ui));
+                self.profiles(ui, &errors);
 
-            ui.horizontal(|ui: &mut egui::Ui| {
-                let mut username_label_id = Id::NULL;
-                ui.horizontal(|ui| {
-                    ui.set_width(70.0);
-                    username_label_id = ui.label("Username: ").id;
+                ui.horizontal(|ui: &mut egui::Ui| {
+                    let mut username_label_id = Id::NULL;
+                    ui.horizontal(|ui| {
+                        ui.set_width(70.0);
+                        username_label_id = ui.label("Username: ").id;
+                    });
+                    ui.text_edit_singleline(&mut *self.changes.username)
+                        .labelled_by(username_label_id);
                 });
-                ui.text_edit_singleline(&mut self.changes.username)
-                    .labelled_by(username_label_id);
-            });
 
-            ui.horizontal(|ui: &mut egui::Ui| {
-                let mut sharecode_label_id = Id::NULL;
-                ui.horizontal(|ui| {
-                    ui.set_width(70.0);
-                    sharecode_label_id = ui.label("Share code: ").id;
+                ui.horizontal(|ui: &mut egui::Ui| {
+                    let mut sharecode_label_id = Id::NULL;
+                    ui.horizontal(|ui| {
+                        ui.set_width(70.0);
+                        sharecode_label_id = ui.label("Share code: ").id;
+                    });
+                    ui.text_edit_singleline(&mut *self.changes.code)
+                        .labelled_by(sharecode_label_id);
                 });
-                ui.text_edit_singleline(&mut self.changes.code)
-                    .labelled_by(sharecode_label_id);
-            });
 
-            ui.horizontal(|ui: &mut egui::Ui| {
-                let mut apikey_label_id = Id::NULL;
-                ui.horizontal(|ui| {
-                    ui.set_width(70.0);
-                    apikey_label_id = ui.label("API key: ").id;
+                ui.horizontal(|ui: &mut egui::Ui| {
+                    let mut apikey_label_id = Id::NULL;
+                    ui.horizontal(|ui| {
+                        ui.set_width(70.0);
+                        apikey_label_id = ui.label("API key: ").id;
+                    });
+                    ui.add(egui::TextEdit::singleline(&mut *self.changes.apikey).password(true))
+                        .labelled_by(apikey_label_id);
+                });
+
+                ui.vertical_centered_justified(|ui| {
+                    ui.separator();
+                    let button = Button::new("Test beep");
+                    if ui.add(button).clicked() {
+                        info!(target: "GUI", "Sending test beep");
+                        let state = self.state.clone();
+                        tokio::spawn(async move {
+                            state
+                                .perform(PiShockOp::Beep { duration: 1 }, ActionTrigger::Manual)
+                                .await;
+                        });
+                    }
                 });
-                ui.text_edit_singleline(&mut self.changes.apikey)
-                    .labelled_by(apikey_label_id);
-            });
 
-            ui.vertical_centered_justified(|ui| {
-                ui.separator();
-                let button = Button::new("Test beep");
-                if ui.add(button).clicked() {
-                    info!(target: "GUI", "Sending test beep");
-                    let c = self.config.clone();
-                    tokio::spawn(async move {
-                        pishock::beep(c, 1).await;
+                ui.vertical_centered(|ui| ui.separator());
+                ui.horizontal(|ui| {
+                    let follow_label = ui.label("Follow: ");
+                    let follow = &mut self.changes.follow;
+                    egui::ComboBox::from_id_source("follow")
+                        .selected_text(match follow {
+                            FollowMode::Own => "Yourself",
+                            FollowMode::Player(_) => "Specific player",
+                            FollowMode::Observed => "Observed player",
+                        })
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(follow, FollowMode::Own, "Yourself");
+                            if ui
+                                .selectable_label(
+                                    matches!(follow, FollowMode::Player(_)),
+                                    "Specific player",
+                                )
+                                .clicked()
+                                && !matches!(follow, FollowMode::Player(_))
+                            {
+                                *follow = FollowMode::Player(String::new());
+                            }
+                            ui.selectable_value(follow, FollowMode::Observed, "Observed player");
+                        })
+                        .response
+                        .labelled_by(follow_label.id);
+                });
+                if let FollowMode::Player(steamid) = &mut self.changes.follow {
+                    ui.horizontal(|ui| {
+                        let mut steamid_label_id = Id::NULL;
+                        ui.horizontal(|ui| {
+                            ui.set_width(70.0);
+                            steamid_label_id = ui.label("SteamID: ").id;
+                        });
+                        ui.add(egui::TextEdit::singleline(steamid).hint_text("7656119..."))
+                            .labelled_by(steamid_label_id);
                     });
+                    field_errors(ui, &errors, &["follow"]);
                 }
-            });
+                ui.vertical_centered(|ui| {
+                    ui.separator();
+                    ui.label("Shock Mode: ");
+                });
+                ui.vertical_centered_justified(|ui| {
+                    ui.selectable_value(&mut self.changes.shock_mode, ShockMode::Random, "Random");
+                    ui.selectable_value(
+                        &mut self.changes.shock_mode,
+                        ShockMode::LastHitPercentage,
+                        "Last Hit Percentage",
+                    );
+                });
+                ui.vertical_centered(|ui| ui.separator());
 
-            ui.vertical_centered(|ui| {
-                ui.separator();
-                ui.label("Shock Mode: ");
-            });
-            ui.vertical_centered_justified(|ui| {
-                ui.selectable_value(&mut self.changes.shock_mode, ShockMode::Random, "Random");
-                ui.selectable_value(
-                    &mut self.changes.shock_mode,
-                    ShockMode::LastHitPercentage,
-                    "Last Hit Percentage",
+                ui.horizontal(|ui| {
+                    let indensity_label = ui.label("Intensity: ");
+
+                    ui.add(
+                        DragValue::new(&mut self.changes.min_intensity)
+                            .speed(1)
+                            .clamp_range(0..=self.changes.max_intensity)
+                            .prefix("Min "),
+                    )
+                    .labelled_by(indensity_label.id);
+                    ui.add(
+                        DragValue::new(&mut self.changes.max_intensity)
+                            .speed(1)
+                            .clamp_range(self.changes.min_intensity..=100)
+                            .prefix("Max "),
+                    )
+                    .labelled_by(indensity_label.id)
+                    .on_hover_text("Nothing stronger is ever sent, whatever the modifiers");
+                });
+                field_errors(
+                    ui,
+                    &errors,
+                    &["min_intensity", "max_intensity", "intensity"],
                 );
-            });
-            ui.vertical_centered(|ui| ui.separator());
-
-            ui.horizontal(|ui| {
-                let indensity_label = ui.label("Intensity: ");
-
-                ui.add(
-                    DragValue::new(&mut self.changes.min_intensity)
-                        .speed(1)
-                        .clamp_range(0..=self.changes.max_intensity)
-                        .prefix("Min "),
-                )
-                .labelled_by(indensity_label.id);
-                ui.add(
-                    DragValue::new(&mut self.changes.max_intensity)
-                        .speed(1)
-                        .clamp_range(self.changes.min_intensity..=100)
-                        .prefix("Max "),
-                )
-                .labelled_by(indensity_label.id);
-            });
-            ui.horizontal(|ui| {
-                let duration_label = ui.label("Duration: ");
-                ui.add(
-                    DragValue::new(&mut self.changes.min_duration)
-                        .speed(1)
-                        .clamp_range(0..=self.changes.max_duration)
-                        .prefix("Min "),
-                )
-                .labelled_by(duration_label.id);
-                ui.add(
-                    DragValue::new(&mut self.changes.max_duration)
-                        .speed(1)
-                        .clamp_range(self.changes.min_duration..=15)
-                        .prefix("Max "),
-                )
-                .labelled_by(duration_label.id);
-            });
+                ui.horizontal(|ui| {
+                    let duration_label = ui.label("Duration: ");
+                    ui.add(
+                        DragValue::new(&mut self.changes.min_duration)
+                            .speed(1)
+                            .clamp_range(0..=self.changes.max_duration)
+                            .prefix("Min "),
+                    )
+                    .labelled_by(duration_label.id);
+                    ui.add(
+                        DragValue::new(&mut self.changes.max_duration)
+                            .speed(1)
+                            .clamp_range(self.changes.min_duration..=15)
+                            .prefix("Max "),
+                    )
+                    .labelled_by(duration_label.id)
+                    .on_hover_text("Nothing longer is ever sent, whatever the modifiers");
+                });
+                field_errors(ui, &errors, &["min_duration", "max_duration", "duration"]);
 
-            ui.add(egui::Checkbox::new(
-                &mut self.changes.beep_on_match_start,
-                "Beep on match start",
-            ));
-            ui.add(egui::Checkbox::new(
-                &mut self.changes.beep_on_round_start,
-                "Beep on round start",
-            ));
+                ui.add(egui::Checkbox::new(
+                    &mut self.changes.beep_on_match_start,
+                    "Beep on match start",
+                ));
+                ui.add(egui::Checkbox::new(
+                    &mut self.changes.beep_on_round_start,
+                    "Beep on round start",
+                ));
 
-            ui.vertical_centered(|ui| {
-                ui.separator();
-            });
+                ui.collapsing("Economy triggers", |ui| {
+                    let trigger = &mut self.changes.expensive_death;
+                    ui.add(egui::Checkbox::new(
+                        &mut trigger.enabled,
+                        "Died with expensive loadout",
+                    ));
+                    ui.add_enabled_ui(trigger.enabled, |ui| {
+                        trigger_op_combo(ui, "expensive_death_op", &mut trigger.op);
+                        ui.horizontal(|ui| {
+                            let equip_label = ui.label("Equip value: ");
+                            ui.add(
+                                DragValue::new(&mut trigger.min_equip_value)
+                                    .speed(50)
+                                    .clamp_range(0..=trigger.max_equip_value - 1)
+                                    .prefix("Min "),
+                            )
+                            .labelled_by(equip_label.id);
+                            ui.add(
+                                DragValue::new(&mut trigger.max_equip_value)
+                                    .speed(50)
+                                    .clamp_range(trigger.min_equip_value + 1..=100000)
+                                    .prefix("Max "),
+                            )
+                            .labelled_by(equip_label.id);
+                        });
+                        field_errors(
+                            ui,
+                            &errors,
+                            &[
+                                "expensive_death.min_equip_value",
+                                "expensive_death.equip_value",
+                            ],
+                        );
+                    });
+
+                    let trigger = &mut self.changes.low_money;
+                    ui.add(egui::Checkbox::new(
+                        &mut trigger.enabled,
+                        "Round start with low money",
+                    ))
+                    .on_hover_text(
+                        "Not on pistol rounds, everyone starts those with the same money",
+                    );
+                    ui.add_enabled_ui(trigger.enabled, |ui| {
+                        trigger_op_combo(ui, "low_money_op", &mut trigger.op);
+                        ui.horizontal(|ui| {
+                            let threshold_label = ui.label("Less than: ");
+                            ui.add(
+                                DragValue::new(&mut trigger.threshold)
+                                    .speed(50)
+                                    .clamp_range(0..=16000)
+                                    .prefix("$"),
+                            )
+                            .labelled_by(threshold_label.id);
+                        });
+                        field_errors(ui, &errors, &["low_money.threshold"]);
+                        ui.horizontal(|ui| {
+                            let intensity_label = ui.label("Intensity: ");
+                            ui.add(
+                                DragValue::new(&mut trigger.intensity)
+                                    .speed(1)
+                                    .clamp_range(0..=100),
+                            )
+                            .labelled_by(intensity_label.id);
+                            let duration_label = ui.label("Duration: ");
+                            ui.add(
+                                DragValue::new(&mut trigger.duration)
+                                    .speed(1)
+                                    .clamp_range(1..=15),
+                            )
+                            .labelled_by(duration_label.id);
+                        });
+                        field_errors(ui, &errors, &["low_money.intensity", "low_money.duration"]);
+                    });
+                });
+
+                ui.collapsing("Game modes", |ui| {
+                    egui::Grid::new("game_modes").striped(true).show(ui, |ui| {
+                        ui.label("Mode");
+                        ui.label("On");
+                        ui.label("Scale");
+                        ui.label("Death");
+                        ui.label("Equip");
+                        ui.label("Eco");
+                        ui.end_row();
+
+                        for (name, filter) in self.changes.game_modes.iter_mut() {
+                            ui.label(name);
+                            ui.checkbox(&mut filter.enabled, "");
+                            ui.add_enabled(
+                                filter.enabled,
+                                DragValue::new(&mut filter.scale)
+                                    .speed(5)
+                                    .clamp_range(0..=1000)
+                                    .suffix("%"),
+                            );
+                            ui.add_enabled(
+                                filter.enabled,
+                                egui::Checkbox::without_text(&mut filter.death),
+                            );
+                            ui.add_enabled(
+                                filter.enabled,
+                                egui::Checkbox::without_text(&mut filter.expensive_death),
+                            );
+                            ui.add_enabled(
+                                filter.enabled,
+                                egui::Checkbox::without_text(&mut filter.low_money),
+                            );
+                            ui.end_row();
+                        }
+                    });
+                    for (name, _) in self.changes.game_modes.iter() {
+                        let field =
+                            format!("game_modes.{}.scale", GameModeFilters::field_name(name));
+                        for error in errors.iter().filter(|error| error.field == field) {
+                            ui.colored_label(
+                                ui.visuals().error_fg_color,
+                                format!("{} scale {}", name, error.message),
+                            );
+                        }
+                    }
+                });
+
+                ui.collapsing("Weapon modifiers", |ui| {
+                    ui.label("Scale death punishment by weapon name or category");
+                    let mut removed = None;
+                    for (i, modifier) in self.changes.weapon_modifiers.iter_mut().enumerate() {
+                        ui.push_id(i, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.add(
+                                    egui::TextEdit::singleline(&mut modifier.pattern)
+                                        .hint_text("awp, Knife, Pistol")
+                                        .desired_width(110.0),
+                                );
+                                ui.add(
+                                    DragValue::new(&mut modifier.scale)
+                                        .speed(5)
+                                        .clamp_range(0..=1000)
+                                        .suffix("%"),
+                                );
+                                if ui.button("Remove").clicked() {
+                                    removed = Some(i);
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                let mut eco_only = modifier.max_equip_value.is_some();
+                                ui.checkbox(&mut eco_only, "Only below equip value");
+                                if eco_only {
+                                    let max_equip_value =
+                                        modifier.max_equip_value.get_or_insert(1000);
+                                    ui.add(
+                                        DragValue::new(max_equip_value)
+                                            .speed(50)
+                                            .clamp_range(0..=100000),
+                                    );
+                                } else {
+                                    modifier.max_equip_value = None;
+                                }
+                            });
+                            field_errors(
+                                ui,
+                                &errors,
+                                &[
+                                    &format!("weapon_modifiers.{}.pattern", i),
+                                    &format!("weapon_modifiers.{}.scale", i),
+                                ],
+                            );
+                        });
+                    }
+                    if let Some(i) = removed {
+                        self.changes.weapon_modifiers.remove(i);
+                    }
+                    if ui.button("Add modifier").clicked() {
+                        self.changes
+                            .weapon_modifiers
+                            .push(WeaponModifier::default());
+                    }
+                });
+
+                ui.collapsing("Presets", |ui| self.presets(ui));
+
+                ui.collapsing("Recording", |ui| {
+                    let recording = &mut self.changes.recording;
+                    ui.checkbox(&mut recording.enabled, "Record GSI payloads");
+                    ui.horizontal(|ui| {
+                        let directory_label = ui.label("Directory: ");
+                        ui.text_edit_singleline(&mut recording.directory)
+                            .labelled_by(directory_label.id)
+                            .on_hover_text(
+                                recording
+                                    .path(self.state.config_path.as_deref())
+                                    .display()
+                                    .to_string(),
+                            );
+                    });
+                    field_errors(ui, &errors, &["recording.directory"]);
+                    ui.horizontal(|ui| {
+                        let size_label = ui.label("Size: ");
+                        ui.add(
+                            DragValue::new(&mut recording.max_file_size_mb)
+                                .speed(1)
+                                .clamp_range(1..=recording.max_total_size_mb)
+                                .prefix("File ")
+                                .suffix(" MB"),
+                        )
+                        .labelled_by(size_label.id);
+                        ui.add(
+                            DragValue::new(&mut recording.max_total_size_mb)
+                                .speed(10)
+                                .clamp_range(recording.max_file_size_mb..=100000)
+                                .prefix("Total ")
+                                .suffix(" MB"),
+                        )
+                        .labelled_by(size_label.id);
+                    });
+                    field_errors(
+                        ui,
+                        &errors,
+                        &["recording.max_file_size_mb", "recording.size"],
+                    );
+                });
 
-            ui.vertical_centered_justified(|ui| {
-                if let Ok(config) = self.config.try_read() {
-                    let changed = config.to_owned() != self.changes;
+                ui.collapsing("Control API", |ui| {
+                    ui.label("Token for /api, leave empty to disable");
+                    ui.horizontal(|ui| {
+                        let token_label = ui.label("Token: ");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut *self.changes.api_token)
+                                .password(true)
+                                .desired_width(140.0),
+                        )
+                        .labelled_by(token_label.id);
+                        if ui.button("Generate").clicked() {
+                            self.changes.api_token = rand::thread_rng()
+                                .sample_iter(&Alphanumeric)
+                                .take(32)
+                                .map(char::from)
+                                .collect::<String>()
+                                .into();
+                        }
+                    });
+                });
 
-                    if ui.add_enabled(changed, Button::new("Reset")).clicked() {
-                        debug!(target: "GUI", "Resetting");
-                        self.changes = config.to_owned();
+                ui.collapsing("Simulator", |ui| {
+                    ui.label("Post synthetic payloads to the local server");
+                    let steps = [
+                        ("Warmup", SimStep::Warmup),
+                        ("Live", SimStep::Live),
+                        ("Freezetime", SimStep::Freezetime),
+                        ("Round live", SimStep::RoundLive),
+                        ("Damage 40", SimStep::Damage(40)),
+                        ("Die", SimStep::Die),
+                        ("Round over", SimStep::RoundOver),
+                        ("Game over", SimStep::GameOver),
+                        ("Leave", SimStep::Leave),
+                    ];
+                    ui.horizontal_wrapped(|ui| {
+                        for (label, step) in steps {
+                            if ui.button(label).clicked() {
+                                let simulator = self.simulator.clone();
+                                tokio::spawn(async move {
+                                    let _ = simulator.lock().await.step(step).await;
+                                });
+                            }
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        for (label, scenario) in [
+                            ("Run death", Scenario::Death),
+                            ("Run match", Scenario::Match),
+                        ] {
+                            if ui.button(label).clicked() {
+                                let simulator = self.simulator.clone();
+                                tokio::spawn(async move {
+                                    let _ = simulator
+                                        .lock()
+                                        .await
+                                        .run_scenario(scenario, Duration::from_secs(1))
+                                        .await;
+                                });
+                            }
+                        }
+                    });
+                    match self
+                        .simulator
+                        .try_lock()
+                        .ok()
+                        .and_then(|simulator| simulator.last_result.clone())
+                    {
+                        Some(Ok(_)) => {
+                            ui.label("Last payload accepted");
+                        }
+                        Some(Err(e)) => {
+                            ui.colored_label(ui.visuals().error_fg_color, e);
+                        }
+                        None => {}
                     }
+                });
+
+                ui.vertical_centered(|ui| {
+                    ui.separator();
+                });
+
+                ui.vertical_centered_justified(|ui| {
+                    let mut save = false;
+                    if let Ok(config) = self.config.try_read() {
+                        let changed = config.to_owned() != self.changes;
+                        let valid = errors.is_empty();
+
+                        if self.conflict && changed {
+                            ui.colored_label(
+                                ui.visuals().warn_fg_color,
+                                "The config was changed outside this window, saving overwrites \
+                                 those changes and resetting discards yours",
+                            );
+                        }
+
+                        if ui.add_enabled(changed, Button::new("Reset")).clicked() {
+                            debug!(target: "GUI", "Resetting");
+                            self.changes = config.to_owned();
+                            self.base = config.to_owned();
+                            self.conflict = false;
+                        }
 
-                    if ui.add_enabled(changed, Button::new("Save")).clicked() {
-                        debug!(target: "GUI", "Saving");
-                        drop(config);
-                        if let Ok(mut owned_config) = self.config.clone().try_write() {
-                            *owned_config = self.changes.clone();
-                            owned_config.write_to_file("config.json");
+                        if ui
+                            .add_enabled(changed && valid, Button::new("Save"))
+                            .on_disabled_hover_text("Fix the settings marked in red to save")
+                            .clicked()
+                        {
+                            save = true;
                         }
                     }
-                }
+                    if save {
+                        self.save();
+                    }
+                    if let Some(e) = &self.save_error {
+                        ui.colored_label(ui.visuals().error_fg_color, e);
+                    }
+
+                    if let Some(path) = &self.state.config_path {
+                        ui.label(
+                            egui::RichText::new(format!("Config: {}", path.display()))
+                                .small()
+                                .weak(),
+                        )
+                        .on_hover_text("Start with --config to use another file");
+                    }
+                });
             });
 
+            // Keep the connection indicator ticking without input
+            ctx.request_repaint_after(Duration::from_secs(1));
+
             if ctx.input(|i| i.viewport().close_requested()) {
                 info!(target: "GUI", "Closing");
                 process::exit(0);
@@ -174,3 +679,375 @@ impl eframe::App for MyApp {
         });
     }
 }
+
+impl MyApp {
+    /// Follow config changes made outside the GUI, unless they would
+    /// overwrite unsaved edits.
+    fn pick_up_reload(&mut self) {
+        let revision = self.state.config_revision.load(Ordering::Relaxed);
+        if revision == self.revision {
+            return;
+        }
+        let Ok(config) = self.config.try_read() else {
+            return;
+        };
+        self.revision = revision;
+
+        if self.changes == self.base {
+            self.changes = config.clone();
+        } else if self.changes != *config {
+            warn!(target: "GUI", "Config changed outside the GUI while there are unsaved edits");
+            self.conflict = true;
+        }
+        self.base = config.clone();
+    }
+
+    fn save(&mut self) {
+        debug!(target: "GUI", "Saving");
+        if let Ok(mut owned_config) = self.config.clone().try_write() {
+            *owned_config = self.changes.clone();
+            self.base = self.changes.clone();
+            self.conflict = false;
+            if let Some(path) = &self.state.config_path {
+                self.save_error = profiles::save(path, &owned_config).err();
+                if let Some(e) = &self.save_error {
+                    error!(target: "GUI", "Failed to save config: {}", e);
+                }
+            }
+        }
+    }
+
+    fn profiles(&mut self, ui: &mut egui::Ui, errors: &[ValidationError]) {
+        let Some(path) = self.state.config_path.clone() else {
+            return;
+        };
+
+        ui.horizontal(|ui| {
+            let profile_label = ui.label("Profile: ");
+            let active = self.changes.profile.clone();
+            egui::ComboBox::from_id_source("profile")
+                .selected_text(if active.is_empty() {
+                    "Default"
+                } else {
+                    &active
+                })
+                .show_ui(ui, |ui| {
+                    let names = std::iter::once(String::new()).chain(profiles::list(&path));
+                    for name in names {
+                        let label = if name.is_empty() { "Default" } else { &name };
+                        if ui.selectable_label(name == active, label).clicked() && name != active {
+                            info!(target: "GUI", "Switching to profile {:?}", name);
+                            let state = self.state.clone();
+                            tokio::spawn(async move {
+                                if let Err(e) = profiles::switch(&state, &name).await {
+                                    error!(target: "GUI", "{}", e);
+                                }
+                            });
+                        }
+                    }
+                })
+                .response
+                .labelled_by(profile_label.id);
+        });
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.new_profile)
+                    .hint_text("New profile")
+                    .desired_width(140.0),
+            );
+            let name = self.new_profile.trim().to_owned();
+            let can_create = profiles::valid_name(&name) && errors.is_empty();
+            if ui
+                .add_enabled(can_create, Button::new("Save as profile"))
+                .on_disabled_hover_text("Letters, digits, - and _ only")
+                .clicked()
+            {
+                self.changes.profile = name;
+                self.new_profile.clear();
+                self.save();
+            }
+        });
+    }
+
+    fn presets(&mut self, ui: &mut egui::Ui) {
+        ui.label("Share triggers and intensities, credentials are never included");
+        ui.horizontal(|ui| {
+            let file_label = ui.label("File: ");
+            ui.text_edit_singleline(&mut self.preset_file)
+                .labelled_by(file_label.id);
+        });
+        ui.horizontal(|ui| {
+            let preset = Preset::from_config(&self.changes);
+            if ui.button("Copy code").clicked() {
+                ui.output_mut(|output| output.copied_text = preset.to_code());
+                self.preset_status = Some(Ok("Preset code copied".to_owned()));
+            }
+            if ui.button("Export").clicked() {
+                self.preset_status = Some(
+                    fs::write(&self.preset_file, preset.to_json())
+                        .map(|_| format!("Exported to {}", self.preset_file))
+                        .map_err(|e| format!("Failed to export: {}", e)),
+                );
+            }
+            if ui.button("Import").clicked() {
+                self.preset_status = match fs::read_to_string(&self.preset_file) {
+                    Ok(json) => {
+                        self.preset_input = json;
+                        None
+                    }
+                    Err(e) => Some(Err(format!("Failed to import: {}", e))),
+                };
+            }
+        });
+        match &self.preset_status {
+            Some(Ok(message)) => {
+                ui.label(message);
+            }
+            Some(Err(e)) => {
+                ui.colored_label(ui.visuals().error_fg_color, e);
+            }
+            None => {}
+        }
+
+        ui.add(
+            egui::TextEdit::multiline(&mut self.preset_input)
+                .hint_text("Paste a preset code")
+                .desired_rows(2),
+        );
+        if self.preset_input.trim().is_empty() {
+            return;
+        }
+
+        let preset = match Preset::parse(&self.preset_input) {
+            Ok(preset) => preset,
+            Err(e) => {
+                ui.colored_label(ui.visuals().error_fg_color, e);
+                return;
+            }
+        };
+
+        ui.label("This preset will:");
+        for line in preset.describe() {
+            ui.label(format!("• {}", line));
+        }
+        let errors = preset.validate(&self.changes);
+        for error in &errors {
+            ui.colored_label(ui.visuals().error_fg_color, error.to_string());
+        }
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(errors.is_empty(), Button::new("Apply"))
+                .on_hover_text("Replaces your triggers and intensities, save to keep them")
+                .clicked()
+            {
+                info!(target: "GUI", "Applying preset");
+                self.changes = preset.apply_to(&self.changes);
+                self.preset_input.clear();
+                self.preset_status = Some(Ok("Preset applied, save to keep it".to_owned()));
+            }
+            if ui.button("Cancel").clicked() {
+                self.preset_input.clear();
+            }
+        });
+    }
+
+    fn game(&self, ui: &mut egui::Ui) {
+        let game = self.game.borrow().clone();
+        if game.steam_id.is_empty() {
+            ui.label("No game data yet");
+            return;
+        }
+
+        egui::Grid::new("game").num_columns(2).show(ui, |ui| {
+            ui.label("Map");
+            match &game.map_name {
+                Some(map) => ui.label(format!("{} ({:?})", map, game.game_mode)),
+                None => ui.label("Not in a match"),
+            };
+            ui.end_row();
+
+            ui.label("Phase");
+            ui.label(format!(
+                "{:?}, round {:?}",
+                game.map_phase, game.round_phase
+            ));
+            ui.end_row();
+
+            ui.label("Client");
+            ui.label(&game.steam_id);
+            ui.end_row();
+
+            let Some(player) = &game.player_state else {
+                ui.label("Player");
+                ui.label("Not following anyone");
+                ui.end_row();
+                return;
+            };
+
+            ui.label("Player");
+            ui.label(&player.name).on_hover_text(&player.steamid);
+            ui.end_row();
+
+            ui.label("Health");
+            ui.label(format!("{} hp, {} armor", player.health, player.armor));
+            ui.end_row();
+
+            ui.label("Score");
+            ui.label(format!("{} kills, {} deaths", player.kills, player.deaths));
+            ui.end_row();
+
+            ui.label("Money");
+            ui.label(format!(
+                "${}, ${} equipped",
+                player.money, player.equip_value
+            ));
+            ui.end_row();
+        });
+    }
+
+    fn history(&mut self, ui: &mut egui::Ui) {
+        let Ok(mut history) = self.state.history.try_lock() else {
+            return;
+        };
+
+        let filter = &mut self.history_filter;
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut filter.executed, "Executed");
+            ui.checkbox(&mut filter.failed, "Failed");
+            ui.checkbox(&mut filter.blocked, "Blocked");
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut filter.events, "Game events");
+            ui.checkbox(&mut filter.manual, "Manual");
+            if ui
+                .add_enabled(!history.is_empty(), Button::new("Clear"))
+                .clicked()
+            {
+                info!(target: "GUI", "Clearing {} actions from the history", history.len());
+                history.clear();
+            }
+        });
+
+        let offset = LOCAL_OFFSET.get().copied().flatten();
+        if offset.is_none() {
+            ui.label("Times are in UTC");
+        }
+        let offset = offset.unwrap_or(UtcOffset::UTC);
+        egui::ScrollArea::vertical()
+            .id_source("history")
+            .max_height(160.0)
+            .show(ui, |ui| {
+                egui::Grid::new("history").striped(true).show(ui, |ui| {
+                    // Newest first
+                    for record in history.iter().rev().filter(|record| filter.shows(record)) {
+                        ui.label(format_time(record.time, offset));
+                        ui.label(match &record.trigger {
+                            ActionTrigger::Event(kind) => kind.as_str(),
+                            ActionTrigger::Manual => "manual",
+                        });
+                        ui.label(match record.op {
+                            PiShockOp::Beep { duration } => format!("beep {}s", duration),
+                            PiShockOp::Vibrate {
+                                intensity,
+                                duration,
+                            }
+                            | PiShockOp::Shock {
+                                intensity,
+                                duration,
+                            } => format!("{} {}% {}s", record.op.name(), intensity, duration),
+                        });
+                        match &record.outcome {
+                            ActionOutcome::Executed => ui.label("executed"),
+                            ActionOutcome::Failed { error } => ui
+                                .colored_label(ui.visuals().error_fg_color, "failed")
+                                .on_hover_text(error),
+                            ActionOutcome::Blocked { reason } => ui
+                                .colored_label(ui.visuals().warn_fg_color, "blocked")
+                                .on_hover_text(reason),
+                        };
+                        ui.end_row();
+                    }
+                });
+            });
+
+        if history.is_empty() {
+            ui.label("Nothing sent yet");
+        }
+    }
+
+    fn status(&self, ui: &mut egui::Ui) {
+        let Ok(health) = self.state.health.try_lock() else {
+            return;
+        };
+
+        let connected = Color32::from_rgb(80, 200, 120);
+        match health.since_last_payload() {
+            Some(_) if health.connected() => {
+                ui.colored_label(connected, "Connected to CS2");
+            }
+            Some(since) => {
+                ui.colored_label(
+                    ui.visuals().error_fg_color,
+                    format!("No data for {}s", since.as_secs()),
+                );
+            }
+            None => {
+                ui.colored_label(ui.visuals().warn_fg_color, "Waiting for CS2");
+            }
+        }
+
+        match health.backend_reachable() {
+            _ if self.state.backend == Backend::DryRun => {
+                ui.label("Dry run, nothing is sent");
+            }
+            Some(true) => {
+                ui.colored_label(connected, "PiShock reachable");
+            }
+            Some(false) => {
+                ui.colored_label(ui.visuals().error_fg_color, "PiShock unreachable");
+            }
+            None => {}
+        }
+
+        if let Some(error) = health.last_error() {
+            ui.colored_label(ui.visuals().error_fg_color, &error.message)
+                .on_hover_text("Last error");
+        }
+    }
+}
+
+/// Show validation errors for `fields` under the widgets they belong to.
+fn field_errors(ui: &mut egui::Ui, errors: &[ValidationError], fields: &[&str]) {
+    for error in errors
+        .iter()
+        .filter(|error| fields.contains(&error.field.as_str()))
+    {
+        ui.colored_label(ui.visuals().error_fg_color, &error.message);
+    }
+}
+
+fn trigger_op_combo(ui: &mut egui::Ui, id: &str, op: &mut TriggerOp) {
+    ui.horizontal(|ui| {
+        ui.label("Action: ");
+        egui::ComboBox::from_id_source(id)
+            .selected_text(format!("{:?}", op))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(op, TriggerOp::Beep, "Beep");
+                ui.selectable_value(op, TriggerOp::Vibrate, "Vibrate");
+                ui.selectable_value(op, TriggerOp::Shock, "Shock");
+            });
+    });
+}
+
+/// Time of day of a [`ActionRecord::time`].
+fn format_time(millis: u64, offset: UtcOffset) -> String {
+    OffsetDateTime::from_unix_timestamp_nanos(millis as i128 * 1_000_000)
+        .map(|time| time.to_offset(offset))
+        .ok()
+        .and_then(|time| {
+            time.format(format_description!("[hour]:[minute]:[second]"))
+                .ok()
+        })
+        .unwrap_or_default()
+}
@@ -0,0 +1,150 @@
+//! Shock yourself when you die in CS2.
+//!
+//! Payloads from CS2 game state integration are parsed into the types in
+//! [`gamestateintegration`], turned into [`events::GameEvent`]s by
+//! [`GameState::update`], evaluated against the [`config::Config`] by
+//! [`rules::evaluate`] and finally sent to a [`backend::Backend`].
+
+pub mod api;
+pub mod backend;
+pub mod config;
+pub mod control;
+pub mod events;
+pub mod feed;
+pub mod gamestateintegration;
+#[cfg(feature = "gui")]
+pub mod gui;
+pub mod lifecycle;
+pub mod metrics;
+pub mod paths;
+pub mod pishock;
+pub mod presets;
+pub mod profiles;
+pub mod recorder;
+pub mod reload;
+pub mod replay;
+pub mod rules;
+pub mod secrets;
+pub mod simulator;
+pub mod status;
+
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64},
+        Arc,
+    },
+};
+
+use backend::Backend;
+use config::Config;
+use feed::{FeedMessage, History, FEED_CAPACITY};
+use gamestateintegration::{GameMode, MapPhase, RoundPhase, Weapon};
+use metrics::Metrics;
+use recorder::Recorder;
+use serde::Serialize;
+use status::Health;
+use tokio::sync::{broadcast, watch, Mutex, RwLock};
+
+pub const NAME: &str = "CS2 Shocker";
+
+/// Shared state of a running instance.
+#[derive(Debug, Clone)]
+pub struct AppState {
+    pub game_state: Arc<Mutex<GameState>>,
+    /// Copy of `game_state` sent after every payload, for anything that
+    /// wants to show it as it changes
+    pub game_updates: watch::Sender<GameState>,
+    pub config: Arc<RwLock<Config>>,
+    /// Where config changes are saved, `None` when they should not persist
+    pub config_path: Option<PathBuf>,
+    /// Bumped whenever the config is replaced by something other than the
+    /// GUI, so it can pick up the change
+    pub config_revision: Arc<AtomicU64>,
+    pub recorder: Arc<Mutex<Recorder>>,
+    pub backend: Backend,
+    /// Blocks actions caused by game events, manual actions still go through
+    pub paused: Arc<AtomicBool>,
+    pub feed: broadcast::Sender<FeedMessage>,
+    /// Recent actions, shown in the GUI
+    pub history: Arc<Mutex<History>>,
+    pub metrics: Arc<Metrics>,
+    pub health: Arc<Mutex<Health>>,
+}
+
+impl AppState {
+    pub fn new(
+        config: Arc<RwLock<Config>>,
+        backend: Backend,
+        config_path: Option<PathBuf>,
+    ) -> Self {
+        Self {
+            game_state: Arc::new(Mutex::new(GameState::default())),
+            game_updates: watch::channel(GameState::default()).0,
+            config,
+            config_revision: Arc::new(AtomicU64::new(0)),
+            recorder: Arc::new(Mutex::new(Recorder::new(config_path.clone()))),
+            config_path,
+            backend,
+            paused: Arc::new(AtomicBool::new(false)),
+            feed: broadcast::channel(FEED_CAPACITY).0,
+            history: Arc::new(Mutex::new(History::default())),
+            metrics: Arc::new(Metrics::default()),
+            health: Arc::new(Mutex::new(Health::default())),
+        }
+    }
+}
+
+/// Everything tracked across payloads to detect events.
+#[derive(Serialize, Debug, Clone)]
+pub struct GameState {
+    pub round_phase: RoundPhase,
+    pub map_phase: MapPhase,
+    pub map_name: Option<String>,
+    pub game_mode: GameMode,
+    /// Halftime was seen, the next round is the pistol round of a new half
+    pub halftime: bool,
+    /// Provider timestamp of the last payload, used to detect reconnects
+    pub last_timestamp: Option<i32>,
+    /// SteamID of the client sending data, not necessarily the player on screen
+    pub steam_id: String,
+    pub player_state: Option<PlayerState>,
+}
+
+/// The followed player as of the previous payload.
+#[derive(Serialize, Debug, Clone)]
+pub struct PlayerState {
+    pub steamid: String,
+    pub name: String,
+    pub health: i32,
+    pub armor: i32,
+    pub kills: i32,
+    pub deaths: i32,
+    pub money: i32,
+    pub equip_value: i32,
+    pub active_weapon: Option<Weapon>,
+}
+
+impl Default for GameState {
+    fn default() -> Self {
+        Self {
+            round_phase: RoundPhase::Unknown,
+            map_phase: MapPhase::Unknown,
+            map_name: None,
+            game_mode: GameMode::Other,
+            halftime: false,
+            last_timestamp: None,
+            steam_id: String::new(),
+            player_state: None,
+        }
+    }
+}
+
+impl GameState {
+    pub fn reset(&mut self) {
+        self.round_phase = RoundPhase::Unknown;
+        self.map_phase = MapPhase::Unknown;
+        self.halftime = false;
+        self.player_state = None;
+    }
+}
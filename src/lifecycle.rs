@@ -0,0 +1,324 @@
+use log::info;
+
+use crate::{
+    gamestateintegration::{Map, MapPhase, Provider},
+    GameState,
+};
+
+/// Payloads further apart than this are treated as a reconnect. Older cfgs
+/// have no heartbeat and can go quiet for minutes while idle, so this is well
+/// above any gap seen while connected.
+const RECONNECT_GAP: i32 = 300;
+
+/// What happened to the match between the previous payload and this one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MatchTransition {
+    /// Nothing worth acting on
+    None,
+    /// Joined a map, either fresh, mid-match or after a reconnect
+    Joined,
+    /// Warmup or a previous match ended and a new one went live
+    Started,
+    /// The match reached game over
+    Ended,
+    /// Left the map, back in the main menu
+    Left,
+}
+
+impl GameState {
+    /// Advance the match state machine.
+    ///
+    /// Any transition into a different match resets the tracked state, so
+    /// death counters from a previous map or session never carry over.
+    pub fn update_match(
+        &mut self,
+        provider: Option<&Provider>,
+        map: Option<&Map>,
+    ) -> MatchTransition {
+        if let Some(provider) = provider {
+            let reconnected = self
+                .last_timestamp
+                .is_some_and(|last| provider.timestamp - last > RECONNECT_GAP);
+            let switched_account = !self.steam_id.is_empty() && self.steam_id != provider.steamid;
+            // A long quiet spell in the same match is not worth a fresh start
+            let same_match = map.is_some_and(|map| {
+                self.map_name.as_deref() == Some(map.name.as_str()) && self.map_phase == map.phase
+            });
+
+            if (reconnected && !same_match) || switched_account {
+                info!("Lost track of the client, waiting for a fresh match state");
+                self.reset();
+                self.map_name = None;
+            } else if reconnected {
+                // Deaths and damage while away must not be punished all at once
+                info!("Lost track of the player, waiting for a fresh player state");
+                self.player_state = None;
+            }
+
+            self.steam_id = provider.steamid.clone();
+            self.last_timestamp = Some(provider.timestamp);
+        }
+
+        let Some(map) = map else {
+            if self.map_name.take().is_some() {
+                self.reset();
+                return MatchTransition::Left;
+            }
+            return MatchTransition::None;
+        };
+
+        let previous_phase = self.map_phase.clone();
+        let transition = if self.map_name.as_deref() != Some(map.name.as_str()) {
+            self.reset();
+            self.map_name = Some(map.name.clone());
+            MatchTransition::Joined
+        } else {
+            match (previous_phase, &map.phase) {
+                (MapPhase::Warmup | MapPhase::GameOver, MapPhase::Live) => {
+                    self.reset();
+                    MatchTransition::Started
+                }
+                (MapPhase::GameOver, MapPhase::Warmup) => {
+                    self.reset();
+                    MatchTransition::None
+                }
+                (previous, MapPhase::GameOver) if previous != MapPhase::GameOver => {
+                    MatchTransition::Ended
+                }
+                _ => MatchTransition::None,
+            }
+        };
+
+        if map.phase == MapPhase::Intermission {
+            self.halftime = true;
+        }
+        self.map_phase = map.phase.clone();
+        self.game_mode = map.mode.clone();
+        transition
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        config::FollowMode,
+        gamestateintegration::{GameMode, Payload},
+        PlayerState,
+    };
+
+    const STEAM_ID: &str = "76561198000000000";
+
+    fn provider(steamid: &str, timestamp: i32) -> Provider {
+        Provider {
+            name: "Counter-Strike: Global Offensive".to_owned(),
+            appid: 730,
+            version: 14000,
+            steamid: steamid.to_owned(),
+            timestamp,
+        }
+    }
+
+    fn map(name: &str, phase: MapPhase) -> Map {
+        Map {
+            mode: GameMode::Competitive,
+            name: name.to_owned(),
+            phase,
+            round: 0,
+        }
+    }
+
+    fn player_state() -> PlayerState {
+        PlayerState {
+            steamid: STEAM_ID.to_owned(),
+            name: "player".to_owned(),
+            health: 100,
+            armor: 0,
+            kills: 3,
+            deaths: 2,
+            money: 800,
+            equip_value: 200,
+            active_weapon: None,
+        }
+    }
+
+    /// A game state on `de_dust2` in `phase`, tracking a player, last heard
+    /// from at timestamp 1000.
+    fn in_match(phase: MapPhase) -> GameState {
+        let mut state = GameState::default();
+        state.update_match(
+            Some(&provider(STEAM_ID, 1000)),
+            Some(&map("de_dust2", phase)),
+        );
+        state.player_state = Some(player_state());
+        state
+    }
+
+    fn update(state: &mut GameState, timestamp: i32, map: Option<Map>) -> MatchTransition {
+        state.update_match(Some(&provider(STEAM_ID, timestamp)), map.as_ref())
+    }
+
+    #[test]
+    fn first_map_is_joined() {
+        let mut state = GameState::default();
+        let transition = update(&mut state, 1000, Some(map("de_dust2", MapPhase::Warmup)));
+
+        assert_eq!(transition, MatchTransition::Joined);
+        assert_eq!(state.map_name.as_deref(), Some("de_dust2"));
+        assert_eq!(state.map_phase, MapPhase::Warmup);
+        assert_eq!(state.steam_id, STEAM_ID);
+    }
+
+    #[test]
+    fn warmup_to_live_starts() {
+        let mut state = in_match(MapPhase::Warmup);
+        let transition = update(&mut state, 1001, Some(map("de_dust2", MapPhase::Live)));
+
+        assert_eq!(transition, MatchTransition::Started);
+        assert!(state.player_state.is_none());
+        assert_eq!(state.map_phase, MapPhase::Live);
+    }
+
+    #[test]
+    fn game_over_to_live_starts() {
+        let mut state = in_match(MapPhase::GameOver);
+        let transition = update(&mut state, 1001, Some(map("de_dust2", MapPhase::Live)));
+
+        assert_eq!(transition, MatchTransition::Started);
+        assert!(state.player_state.is_none());
+    }
+
+    #[test]
+    fn game_over_ends_once() {
+        let mut state = in_match(MapPhase::Live);
+        let transition = update(&mut state, 1001, Some(map("de_dust2", MapPhase::GameOver)));
+        assert_eq!(transition, MatchTransition::Ended);
+        // Stats stay visible on the scoreboard until the next match
+        assert!(state.player_state.is_some());
+
+        let transition = update(&mut state, 1002, Some(map("de_dust2", MapPhase::GameOver)));
+        assert_eq!(transition, MatchTransition::None);
+    }
+
+    #[test]
+    fn game_over_to_warmup_resets() {
+        let mut state = in_match(MapPhase::GameOver);
+        let transition = update(&mut state, 1001, Some(map("de_dust2", MapPhase::Warmup)));
+
+        assert_eq!(transition, MatchTransition::None);
+        assert!(state.player_state.is_none());
+        assert_eq!(state.map_phase, MapPhase::Warmup);
+    }
+
+    #[test]
+    fn live_payloads_change_nothing() {
+        let mut state = in_match(MapPhase::Live);
+        let transition = update(&mut state, 1001, Some(map("de_dust2", MapPhase::Live)));
+
+        assert_eq!(transition, MatchTransition::None);
+        assert!(state.player_state.is_some());
+    }
+
+    #[test]
+    fn map_change_joins() {
+        let mut state = in_match(MapPhase::Live);
+        let transition = update(&mut state, 1001, Some(map("de_mirage", MapPhase::Warmup)));
+
+        assert_eq!(transition, MatchTransition::Joined);
+        assert_eq!(state.map_name.as_deref(), Some("de_mirage"));
+        assert!(state.player_state.is_none());
+    }
+
+    #[test]
+    fn leaving_the_map_leaves_once() {
+        let mut state = in_match(MapPhase::Live);
+        assert_eq!(update(&mut state, 1001, None), MatchTransition::Left);
+        assert!(state.map_name.is_none());
+        assert!(state.player_state.is_none());
+
+        assert_eq!(update(&mut state, 1002, None), MatchTransition::None);
+    }
+
+    #[test]
+    fn account_switch_rejoins() {
+        let mut state = in_match(MapPhase::Live);
+        let transition = state.update_match(
+            Some(&provider("76561198000000001", 1001)),
+            Some(&map("de_dust2", MapPhase::Live)),
+        );
+
+        assert_eq!(transition, MatchTransition::Joined);
+        assert_eq!(state.steam_id, "76561198000000001");
+        assert!(state.player_state.is_none());
+    }
+
+    #[test]
+    fn reconnect_to_another_phase_rejoins() {
+        let mut state = in_match(MapPhase::Warmup);
+        let transition = update(
+            &mut state,
+            1000 + RECONNECT_GAP + 1,
+            Some(map("de_dust2", MapPhase::Live)),
+        );
+
+        assert_eq!(transition, MatchTransition::Joined);
+        assert!(state.player_state.is_none());
+    }
+
+    #[test]
+    fn quiet_spell_in_the_same_match_keeps_the_match() {
+        let mut state = in_match(MapPhase::Live);
+        let transition = update(
+            &mut state,
+            1000 + RECONNECT_GAP + 1,
+            Some(map("de_dust2", MapPhase::Live)),
+        );
+
+        assert_eq!(transition, MatchTransition::None);
+        assert_eq!(state.map_name.as_deref(), Some("de_dust2"));
+        assert_eq!(state.map_phase, MapPhase::Live);
+        assert!(state.player_state.is_none());
+        assert_eq!(state.last_timestamp, Some(1000 + RECONNECT_GAP + 1));
+    }
+
+    #[test]
+    fn deaths_during_a_quiet_spell_are_not_punished() {
+        let mut state = in_match(MapPhase::Live);
+        let payload = serde_json::from_value::<Payload>(json!({
+            "provider": provider(STEAM_ID, 1000 + RECONNECT_GAP + 1),
+            "map": map("de_dust2", MapPhase::Live),
+            "player": {
+                "steamid": STEAM_ID,
+                "name": "player",
+                "state": {
+                    "health": 40, "armor": 0, "helmet": false, "flashed": 0,
+                    "smoked": 0, "burning": 0, "money": 800, "round_kills": 0,
+                    "round_killhs": 0, "equip_value": 200,
+                },
+                "match_stats": { "kills": 3, "assists": 0, "deaths": 5, "mvps": 0, "score": 0 },
+            },
+        }))
+        .unwrap();
+
+        let events = state.update(&payload, &FollowMode::Own);
+        assert!(events.is_empty(), "{:?}", events);
+        assert_eq!(
+            state.player_state.as_ref().map(|player| player.deaths),
+            Some(5)
+        );
+    }
+
+    #[test]
+    fn gaps_within_the_limit_are_no_reconnect() {
+        let mut state = in_match(MapPhase::Warmup);
+        let transition = update(
+            &mut state,
+            1000 + RECONNECT_GAP,
+            Some(map("de_dust2", MapPhase::Live)),
+        );
+
+        assert_eq!(transition, MatchTransition::Started);
+    }
+}
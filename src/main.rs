@@ -1,67 +1,67 @@
-mod api;
-mod config;
-mod gamestateintegration;
-mod gui;
-mod pishock;
-
-use std::{
-    fs::File,
-    io::{Error, Read},
-    sync::Arc,
+use std::{path::PathBuf, sync::Arc};
+
+use clap::{Parser, Subcommand};
+#[cfg(feature = "gui")]
+use cs2shock::gui;
+use cs2shock::{
+    api,
+    backend::Backend,
+    config::Config,
+    paths, profiles, reload,
+    replay::{self, ReplayArgs},
+    secrets,
+    simulator::{self, SimulateArgs},
+    AppState, NAME,
 };
-
-use config::Config;
-use gamestateintegration::{MapPhase, RoundPhase};
 use log::{error, info};
 use simple_logger::SimpleLogger;
 use time::macros::format_description;
-use tokio::sync::{Mutex, RwLock};
-
-pub const NAME: &str = "CS2 Shocker";
-
-#[derive(Debug, Clone)]
-struct AppState {
-    game_state: Arc<Mutex<GameState>>,
-    config: Arc<RwLock<Config>>,
+use tokio::sync::RwLock;
+
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Run the GSI server without the settings window
+    #[arg(long)]
+    headless: bool,
+    /// Config file to load and save, defaults to config.json in the platform
+    /// config directory
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Switch to this profile, it is saved as the profile to use from now on
+    #[arg(long)]
+    profile: Option<String>,
+    /// Log device operations instead of sending them to PiShock
+    #[arg(long)]
+    dry_run: bool,
+    /// Port the GSI server listens on, must match the uri in the GSI cfg
+    #[arg(long, default_value_t = 3000)]
+    port: u16,
 }
 
-#[derive(Debug, Clone)]
-struct GameState {
-    round_phase: RoundPhase,
-    map_phase: MapPhase,
-    steam_id: String,
-    player_state: Option<PlayerState>,
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Replay a recorded GSI session through the trigger pipeline
+    Replay(ReplayArgs),
+    /// Post a scripted scenario of synthetic payloads to a running instance
+    Simulate(SimulateArgs),
 }
 
-#[derive(Debug, Clone)]
-struct PlayerState {
-    health: i32,
-    armor: i32,
-    kills: i32,
-    deaths: i32,
-}
+fn main() {
+    // Reading the local offset is only possible while single threaded
+    #[cfg(feature = "gui")]
+    gui::capture_local_offset();
 
-impl Default for GameState {
-    fn default() -> Self {
-        Self {
-            round_phase: RoundPhase::Unknown,
-            map_phase: MapPhase::Unknown,
-            steam_id: String::new(),
-            player_state: None,
-        }
-    }
+    tokio::runtime::Runtime::new()
+        .expect("Failed to start the async runtime")
+        .block_on(run());
 }
 
-impl GameState {
-    fn reset(&mut self) {
-        self.round_phase = RoundPhase::Unknown;
-        self.map_phase = MapPhase::Unknown;
-        self.player_state = None;
-    }
-}
+async fn run() {
+    let cli = Cli::parse();
 
-#[tokio::main]
-async fn main() {
     SimpleLogger::new()
         .env()
         .with_level(log::LevelFilter::Info)
@@ -71,36 +71,94 @@ async fn main() {
         .init()
         .expect("Failed to initialize logger");
 
-    let config = || -> Result<Config, Error> {
-        let mut file = File::open("config.json")?;
-        let mut raw = String::new();
-        file.read_to_string(&mut raw)?;
-        let conf = serde_json::from_str::<Config>(&raw)?;
-        info!("Config file loaded");
-        Ok(conf)
-    }();
-
-    let mut config = if let Ok(c) = config {
-        Arc::new(RwLock::new(c))
-    } else {
-        Arc::new(RwLock::new(Config::default()))
-    };
+    if let Some(Command::Simulate(args)) = cli.command {
+        simulator::run(args).await;
+        return;
+    }
+
+    let config_path = paths::resolve_config(cli.config);
+    info!("Using config {}", config_path.display());
 
-    if !config.read().await.validate() {
-        config = Arc::new(RwLock::new(Config::default()));
-        error!("Invalid config, using default");
+    let mut config = match Config::load(&config_path) {
+        Ok(Some(config)) => {
+            info!("Config file loaded");
+            config
+        }
+        result => {
+            if let Err(e) = result {
+                error!("{}, using default", e);
+            }
+            // Secrets can still come from secrets.json or the environment
+            let mut config = Config::default();
+            if let Err(e) = secrets::load(&config_path, &mut config) {
+                error!("{}", e);
+            }
+            secrets::apply_env(&mut config);
+            config
+        }
+    };
+    // Fall back to the saved profile, then to none, when a profile is missing
+    let mut wanted = cli.profile.iter().cloned().collect::<Vec<_>>();
+    wanted.push(config.profile.clone());
+    wanted.push(String::new());
+    for name in wanted {
+        match profiles::apply(&config_path, &config, &name) {
+            Ok(applied) => {
+                if !name.is_empty() {
+                    info!("Using profile {}", name);
+                }
+                config = applied;
+                break;
+            }
+            Err(e) => error!("{}, not using it", e),
+        }
+    }
+    for error in config.reset_invalid() {
+        error!(target: "Config", "{}, using default", error);
     }
+    let config = Arc::new(RwLock::new(config));
 
     info!("Config: \n{:?}", config);
 
     info!("{} v{}", NAME, env!("CARGO_PKG_VERSION"));
 
-    let c = config.clone();
+    if let Some(Command::Replay(args)) = cli.command {
+        if let Err(e) = replay::run(config, args).await {
+            error!("Replay failed: {}", e);
+        }
+        return;
+    }
+
+    let backend = if cli.dry_run {
+        Backend::DryRun
+    } else {
+        Backend::PiShock
+    };
+
+    // Reloads start from the config file, which has to agree. A profile that
+    // failed to apply leaves the saved one be.
+    let profile = config.read().await.profile.clone();
+    if let Some(requested) = cli.profile.as_ref().filter(|name| **name == profile) {
+        if let Err(e) = profiles::remember(&config_path, requested) {
+            error!("Failed to remember profile {}: {}", requested, e);
+        }
+    }
+
+    let state = AppState::new(config, backend, Some(config_path));
+    reload::spawn(state.clone());
 
-    let task = tokio::spawn(async move {
-        api::run(c).await;
-    });
+    #[cfg(feature = "gui")]
+    if !cli.headless {
+        let s = state.clone();
+
+        let task = tokio::spawn(async move {
+            api::run(s, cli.port).await;
+        });
+
+        gui::run(state, cli.port).await;
+        task.await.unwrap();
+        return;
+    }
 
-    gui::run(config.clone()).await;
-    task.await.unwrap();
+    api::run(state, cli.port).await;
 }
@@ -0,0 +1,228 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicI64, AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use axum::{extract::State, http::header::CONTENT_TYPE, response::IntoResponse};
+
+use crate::{feed::ActionOutcome, pishock::PiShockOp, AppState};
+
+/// Upper bounds of the PiShock latency histogram buckets, in seconds.
+const LATENCY_BUCKETS: [f64; 8] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+#[derive(Debug, Default)]
+struct Histogram {
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+/// Counters exposed in the Prometheus text format on `GET /metrics`.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    payloads_received: AtomicU64,
+    parse_failures: AtomicU64,
+    /// Actions waiting for or in the middle of being sent
+    queue_depth: AtomicI64,
+    events: Mutex<BTreeMap<&'static str, u64>>,
+    actions: Mutex<BTreeMap<(&'static str, &'static str), u64>>,
+    intensity_seconds: Mutex<BTreeMap<&'static str, u64>>,
+    pishock_latency: Mutex<Histogram>,
+}
+
+impl Metrics {
+    pub fn payload_received(&self) {
+        self.payloads_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn parse_failed(&self) {
+        self.parse_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn event(&self, kind: &'static str) {
+        if let Ok(mut events) = self.events.lock() {
+            *events.entry(kind).or_default() += 1;
+        }
+    }
+
+    pub fn action_queued(&self) {
+        self.queue_depth.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn action_finished(&self, op: &PiShockOp, outcome: &ActionOutcome) {
+        self.queue_depth.fetch_sub(1, Ordering::Relaxed);
+
+        if let Ok(mut actions) = self.actions.lock() {
+            *actions.entry((op.name(), outcome.status())).or_default() += 1;
+        }
+
+        if *outcome != ActionOutcome::Executed {
+            return;
+        }
+
+        let delivered = match op {
+            PiShockOp::Beep { .. } => return,
+            PiShockOp::Vibrate {
+                intensity,
+                duration,
+            }
+            | PiShockOp::Shock {
+                intensity,
+                duration,
+            } => (intensity * duration).max(0) as u64,
+        };
+
+        if let Ok(mut intensity_seconds) = self.intensity_seconds.lock() {
+            *intensity_seconds.entry(op.name()).or_default() += delivered;
+        }
+    }
+
+    pub fn pishock_latency(&self, latency: Duration) {
+        let seconds = latency.as_secs_f64();
+        if let Ok(mut histogram) = self.pishock_latency.lock() {
+            for (bucket, bound) in histogram.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+                if seconds <= bound {
+                    *bucket += 1;
+                }
+            }
+            histogram.sum += seconds;
+            histogram.count += 1;
+        }
+    }
+
+    /// Render every metric in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "cs2shock_payloads_received_total",
+            "counter",
+            "GSI payloads received on /data",
+        );
+        let _ = writeln!(
+            out,
+            "cs2shock_payloads_received_total {}",
+            self.payloads_received.load(Ordering::Relaxed)
+        );
+
+        header(
+            &mut out,
+            "cs2shock_payload_parse_failures_total",
+            "counter",
+            "GSI payloads that could not be parsed",
+        );
+        let _ = writeln!(
+            out,
+            "cs2shock_payload_parse_failures_total {}",
+            self.parse_failures.load(Ordering::Relaxed)
+        );
+
+        header(
+            &mut out,
+            "cs2shock_events_total",
+            "counter",
+            "Game events detected, by type",
+        );
+        if let Ok(events) = self.events.lock() {
+            for (kind, count) in events.iter() {
+                let _ = writeln!(out, "cs2shock_events_total{{type=\"{}\"}} {}", kind, count);
+            }
+        }
+
+        header(
+            &mut out,
+            "cs2shock_actions_total",
+            "counter",
+            "Device actions, by op and outcome",
+        );
+        if let Ok(actions) = self.actions.lock() {
+            for ((op, outcome), count) in actions.iter() {
+                let _ = writeln!(
+                    out,
+                    "cs2shock_actions_total{{op=\"{}\",outcome=\"{}\"}} {}",
+                    op, outcome, count
+                );
+            }
+        }
+
+        header(
+            &mut out,
+            "cs2shock_intensity_seconds_total",
+            "counter",
+            "Intensity multiplied by duration of executed actions, by op",
+        );
+        if let Ok(intensity_seconds) = self.intensity_seconds.lock() {
+            for (op, total) in intensity_seconds.iter() {
+                let _ = writeln!(
+                    out,
+                    "cs2shock_intensity_seconds_total{{op=\"{}\"}} {}",
+                    op, total
+                );
+            }
+        }
+
+        header(
+            &mut out,
+            "cs2shock_action_queue_depth",
+            "gauge",
+            "Device actions waiting for or in the middle of being sent",
+        );
+        let _ = writeln!(
+            out,
+            "cs2shock_action_queue_depth {}",
+            self.queue_depth.load(Ordering::Relaxed)
+        );
+
+        header(
+            &mut out,
+            "cs2shock_pishock_request_duration_seconds",
+            "histogram",
+            "Latency of PiShock API requests",
+        );
+        if let Ok(histogram) = self.pishock_latency.lock() {
+            for (count, bound) in histogram.buckets.iter().zip(LATENCY_BUCKETS) {
+                let _ = writeln!(
+                    out,
+                    "cs2shock_pishock_request_duration_seconds_bucket{{le=\"{}\"}} {}",
+                    bound, count
+                );
+            }
+            let _ = writeln!(
+                out,
+                "cs2shock_pishock_request_duration_seconds_bucket{{le=\"+Inf\"}} {}",
+                histogram.count
+            );
+            let _ = writeln!(
+                out,
+                "cs2shock_pishock_request_duration_seconds_sum {}",
+                histogram.sum
+            );
+            let _ = writeln!(
+                out,
+                "cs2shock_pishock_request_duration_seconds_count {}",
+                histogram.count
+            );
+        }
+
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// `GET /metrics`
+pub async fn metrics(State(state): State<AppState>) -> impl IntoResponse {
+    (
+        [(CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(),
+    )
+}
@@ -0,0 +1,164 @@
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use log::{error, info};
+use serde_json::{Map, Value};
+
+use crate::{profiles, secrets};
+
+/// Name of the config file, both in the platform config directory and the
+/// working directory older versions used.
+pub const CONFIG_FILE: &str = "config.json";
+
+/// Where the config lives by default, `~/.config/cs2shock` on Linux,
+/// `%APPDATA%\cs2shock` on Windows and `~/Library/Application Support/cs2shock`
+/// on macOS. `None` when the platform has no config directory.
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("cs2shock"))
+}
+
+/// Pick the config file, an explicit path always wins. Otherwise the file in
+/// the platform config directory is used, copying a `config.json` from next
+/// to the executable or the working directory there the first time.
+pub fn resolve_config(explicit: Option<PathBuf>) -> PathBuf {
+    if let Some(path) = explicit {
+        return path;
+    }
+
+    let Some(dir) = config_dir() else {
+        return PathBuf::from(CONFIG_FILE);
+    };
+    if let Err(e) = fs::create_dir_all(&dir) {
+        error!(target: "Config", "Failed to create {}: {}", dir.display(), e);
+        return PathBuf::from(CONFIG_FILE);
+    }
+    let path = dir.join(CONFIG_FILE);
+    if path.exists() {
+        return path;
+    }
+
+    let Some(old) = old_config() else {
+        return path;
+    };
+    match migrate(&old, &path) {
+        Ok(_) => info!(
+            target: "Config",
+            "Copied {} to {}, the old one is no longer used",
+            old.display(),
+            path.display()
+        ),
+        Err(e) => {
+            error!(
+                target: "Config",
+                "Failed to copy {} to {}, using it where it is: {}",
+                old.display(),
+                path.display(),
+                e
+            );
+            return old;
+        }
+    }
+
+    path
+}
+
+/// A `config.json` left by a version that kept it next to the executable or
+/// in the working directory.
+fn old_config() -> Option<PathBuf> {
+    let next_to_exe = env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.join(CONFIG_FILE)));
+
+    next_to_exe
+        .into_iter()
+        .chain([PathBuf::from(CONFIG_FILE)])
+        .find(|path| is_config(path))
+}
+
+/// Other programs use `config.json` too, only take files that look like ours.
+fn is_config(path: &Path) -> bool {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|raw| serde_json::from_str::<Map<String, Value>>(&raw).ok())
+        .is_some_and(|config| {
+            config.contains_key("shock_mode") && config.contains_key("min_intensity")
+        })
+}
+
+/// Replace a file by writing a temporary file next to it and renaming that
+/// over the original, so readers never see a half-written file. `private`
+/// files are only readable by the current user.
+pub fn write_atomic(path: &Path, contents: &[u8], private: bool) -> io::Result<()> {
+    // Every write gets its own temporary file, saves from the GUI and the
+    // control API can run at the same time
+    static WRITES: AtomicU64 = AtomicU64::new(0);
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(
+        ".{}.{}.tmp",
+        process::id(),
+        WRITES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let tmp = PathBuf::from(tmp);
+
+    let write = || -> io::Result<()> {
+        let mut options = OpenOptions::new();
+        options.create(true).write(true).truncate(true);
+        #[cfg(unix)]
+        if private {
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        }
+
+        let mut file = options.open(&tmp)?;
+        if private {
+            secrets::restrict(&tmp)?;
+        }
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    };
+
+    write().inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
+/// Copy a config file along with its secrets and profiles. The originals are
+/// left where they are, in case they belong to something else after all.
+fn migrate(from: &Path, to: &Path) -> io::Result<()> {
+    if let Some(dir) = to.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut copied = vec![(from.to_owned(), to.to_owned())];
+    let secrets = secrets::path(from);
+    if secrets.exists() {
+        copied.push((secrets, secrets::path(to)));
+    }
+    let profiles = profiles::directory(from);
+    if profiles.is_dir() {
+        let target = profiles::directory(to);
+        fs::create_dir_all(&target)?;
+        for entry in fs::read_dir(&profiles)? {
+            let entry = entry?;
+            if entry.path().is_file() {
+                copied.push((entry.path(), target.join(entry.file_name())));
+            }
+        }
+    }
+
+    // The config goes last, so a failure part way is retried on the next start
+    for (from, to) in copied.iter().rev() {
+        fs::copy(from, to)?;
+    }
+    if secrets::path(to).exists() {
+        secrets::restrict(&secrets::path(to))?;
+    }
+
+    Ok(())
+}
@@ -1,17 +1,34 @@
-use std::sync::Arc;
-
 use log::{debug, error, info};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
-use tokio::sync::RwLock;
 
-use crate::{config::Config, NAME};
+use crate::{config::Config, secrets::Secret, NAME};
+
+/// What the PiShock API needs to reach a shocker, copied out of the config so
+/// no lock is held while a request is in flight.
+#[derive(Debug, Clone)]
+pub struct Credentials {
+    pub username: Secret,
+    pub code: Secret,
+    pub apikey: Secret,
+}
 
-pub async fn shock(config: Arc<RwLock<Config>>, intensity: i32, duration: i32) {
+impl Credentials {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            username: config.username.clone(),
+            code: config.code.clone(),
+            apikey: config.apikey.clone(),
+        }
+    }
+}
+
+/// Shock and log the outcome.
+pub async fn shock(credentials: Credentials, intensity: i32, duration: i32) -> Result<i32, String> {
     debug!( target: "PiShock API", "Sending shock: {}, {}", intensity, duration);
 
     let res = post(
-        config,
+        credentials,
         PiShockOp::Shock {
             intensity,
             duration,
@@ -19,7 +36,7 @@ pub async fn shock(config: Arc<RwLock<Config>>, intensity: i32, duration: i32) {
     )
     .await;
 
-    match res {
+    match &res {
         Ok(_) => {
             info!( target: "PiShock API",
                 "Successfully sent shock (intensity: {}, duration: {})", intensity, duration
@@ -29,15 +46,22 @@ pub async fn shock(config: Arc<RwLock<Config>>, intensity: i32, duration: i32) {
             error!( target: "PiShock API", "Failed to send shock: {}", e);
         }
     }
+
+    res
 }
 
-pub async fn vibrate(config: Arc<RwLock<Config>>, intensity: i32, duration: i32) {
+/// Vibrate and log the outcome.
+pub async fn vibrate(
+    credentials: Credentials,
+    intensity: i32,
+    duration: i32,
+) -> Result<i32, String> {
     debug!( target: "PiShock API",
         "Sending vibrate: {}, {}", intensity, duration
     );
 
     let res = post(
-        config,
+        credentials,
         PiShockOp::Vibrate {
             intensity,
             duration,
@@ -45,7 +69,7 @@ pub async fn vibrate(config: Arc<RwLock<Config>>, intensity: i32, duration: i32)
     )
     .await;
 
-    match res {
+    match &res {
         Ok(_) => {
             info!(
                 target: "PiShock API",
@@ -56,14 +80,17 @@ pub async fn vibrate(config: Arc<RwLock<Config>>, intensity: i32, duration: i32)
             error!( target: "PiShock API", "Failed to send vibrate: {}", e);
         }
     }
+
+    res
 }
 
-pub async fn beep(config: Arc<RwLock<Config>>, duration: i32) {
+/// Beep and log the outcome.
+pub async fn beep(credentials: Credentials, duration: i32) -> Result<i32, String> {
     debug!( target: "PiShock API", "Sending beep: {}", duration);
 
-    let res = post(config, PiShockOp::Beep { duration }).await;
+    let res = post(credentials, PiShockOp::Beep { duration }).await;
 
-    match res {
+    match &res {
         Ok(_) => {
             info!(
                         target: "PiShock API",
@@ -74,21 +101,44 @@ pub async fn beep(config: Arc<RwLock<Config>>, duration: i32) {
             error!( target: "PiShock API", "Failed to send beep: {}", e);
         }
     }
+
+    res
 }
 
-pub async fn post(config: Arc<RwLock<Config>>, body: PiShockOp) -> Result<i32, String> {
-    let mut raw_body = Value::Object(Map::new());
+/// Send any operation, logging the outcome.
+pub async fn send(credentials: Credentials, op: PiShockOp) -> Result<i32, String> {
+    match op {
+        PiShockOp::Beep { duration } => beep(credentials, duration).await,
+        PiShockOp::Vibrate {
+            intensity,
+            duration,
+        } => vibrate(credentials, intensity, duration).await,
+        PiShockOp::Shock {
+            intensity,
+            duration,
+        } => shock(credentials, intensity, duration).await,
+    }
+}
 
-    let config = config.read().await;
+/// Send an operation to the PiShock API, returning the HTTP status on
+/// success.
+pub async fn post(credentials: Credentials, body: PiShockOp) -> Result<i32, String> {
+    let mut raw_body = Value::Object(Map::new());
 
     if let Value::Object(inner) = &mut raw_body {
         inner.insert(
             "Username".to_owned(),
-            Value::String(config.username.clone()),
+            Value::String(credentials.username.to_string()),
         );
         inner.insert("Name".to_owned(), Value::String(NAME.to_string()));
-        inner.insert("Code".to_owned(), Value::String(config.code.clone()));
-        inner.insert("Apikey".to_owned(), Value::String(config.apikey.clone()));
+        inner.insert(
+            "Code".to_owned(),
+            Value::String(credentials.code.to_string()),
+        );
+        inner.insert(
+            "Apikey".to_owned(),
+            Value::String(credentials.apikey.to_string()),
+        );
 
         match body {
             PiShockOp::Beep { duration } => {
@@ -125,24 +175,33 @@ pub async fn post(config: Arc<RwLock<Config>>, body: PiShockOp) -> Result<i32, S
     match res {
         Ok(res) => {
             if res.status().is_success() {
-                return Ok(res.status().as_u16().clone() as i32);
+                Ok(res.status().as_u16() as i32)
             } else {
-                return Err(format!(
+                Err(format!(
                     "Failed to post to pishock: {}",
-                    res.status().as_u16().clone()
-                ));
+                    res.status().as_u16()
+                ))
             }
         }
-        Err(e) => {
-            return Err(e.to_string());
-        }
+        Err(e) => Err(e.to_string()),
     }
 }
 
-#[derive(Serialize, Debug, Clone)]
+/// A device operation, intensity is 0-100 and duration is in seconds.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "PascalCase")]
 pub enum PiShockOp {
     Beep { duration: i32 },
     Vibrate { intensity: i32, duration: i32 },
     Shock { intensity: i32, duration: i32 },
 }
+
+impl PiShockOp {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PiShockOp::Beep { .. } => "beep",
+            PiShockOp::Vibrate { .. } => "vibrate",
+            PiShockOp::Shock { .. } => "shock",
+        }
+    }
+}
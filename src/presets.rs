@@ -0,0 +1,345 @@
+//! Shareable presets of the trigger and intensity settings.
+//!
+//! A preset never includes credentials, the API token, who is followed or
+//! where recordings go. It is shared either as a JSON file or as a code,
+//! `cs2shock:` followed by the same JSON encoded as URL safe base64.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{
+    Config, ExpensiveDeathTrigger, GameModeFilters, LowMoneyTrigger, ShockMode, ValidationError,
+    WeaponModifier,
+};
+
+/// Prefix of preset codes, so they are recognizable when pasted.
+pub const CODE_PREFIX: &str = "cs2shock:";
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Preset {
+    pub shock_mode: ShockMode,
+    pub min_duration: i32,
+    pub max_duration: i32,
+    pub min_intensity: i32,
+    pub max_intensity: i32,
+    pub beep_on_match_start: bool,
+    pub beep_on_round_start: bool,
+    pub expensive_death: ExpensiveDeathTrigger,
+    pub low_money: LowMoneyTrigger,
+    pub weapon_modifiers: Vec<WeaponModifier>,
+    pub game_modes: GameModeFilters,
+}
+
+impl Default for Preset {
+    fn default() -> Self {
+        Self::from_config(&Config::default())
+    }
+}
+
+impl Preset {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            shock_mode: config.shock_mode.clone(),
+            min_duration: config.min_duration,
+            max_duration: config.max_duration,
+            min_intensity: config.min_intensity,
+            max_intensity: config.max_intensity,
+            beep_on_match_start: config.beep_on_match_start,
+            beep_on_round_start: config.beep_on_round_start,
+            expensive_death: config.expensive_death.clone(),
+            low_money: config.low_money.clone(),
+            weapon_modifiers: config.weapon_modifiers.clone(),
+            game_modes: config.game_modes.clone(),
+        }
+    }
+
+    /// `config` with this preset applied, everything not in a preset is kept.
+    pub fn apply_to(&self, config: &Config) -> Config {
+        Config {
+            shock_mode: self.shock_mode.clone(),
+            min_duration: self.min_duration,
+            max_duration: self.max_duration,
+            min_intensity: self.min_intensity,
+            max_intensity: self.max_intensity,
+            beep_on_match_start: self.beep_on_match_start,
+            beep_on_round_start: self.beep_on_round_start,
+            expensive_death: self.expensive_death.clone(),
+            low_money: self.low_money.clone(),
+            weapon_modifiers: self.weapon_modifiers.clone(),
+            game_modes: self.game_modes.clone(),
+            ..config.clone()
+        }
+    }
+
+    /// Problems the preset would cause when applied to `config`.
+    pub fn validate(&self, config: &Config) -> Vec<ValidationError> {
+        self.apply_to(config).validate()
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Preset always serializes")
+    }
+
+    pub fn to_code(&self) -> String {
+        let json = serde_json::to_string(self).expect("Preset always serializes");
+        format!("{}{}", CODE_PREFIX, URL_SAFE_NO_PAD.encode(json))
+    }
+
+    /// Parse a preset code or the JSON of a preset file.
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let input = input.trim();
+        let json = match input.strip_prefix(CODE_PREFIX) {
+            Some(code) => {
+                let bytes = URL_SAFE_NO_PAD
+                    .decode(code.trim_end_matches('='))
+                    .map_err(|e| format!("Invalid preset code: {}", e))?;
+                String::from_utf8(bytes).map_err(|_| "Invalid preset code".to_owned())?
+            }
+            None => input.to_owned(),
+        };
+
+        serde_json::from_str(&json).map_err(|e| format!("Invalid preset: {}", e))
+    }
+
+    /// Human readable summary of what the preset does, one line per trigger.
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        lines.push(match self.shock_mode {
+            ShockMode::Random => format!(
+                "Death: shock {}-{}% for {}-{}s",
+                self.min_intensity, self.max_intensity, self.min_duration, self.max_duration
+            ),
+            ShockMode::LastHitPercentage => format!(
+                "Death: shock up to {}% for up to {}s, scaled by health before dying",
+                self.max_intensity, self.max_duration
+            ),
+        });
+
+        if self.beep_on_match_start {
+            lines.push("Beep on match start".to_owned());
+        }
+        if self.beep_on_round_start {
+            lines.push("Beep on round start".to_owned());
+        }
+
+        let trigger = &self.expensive_death;
+        if trigger.enabled {
+            lines.push(format!(
+                "Expensive death: {:?}, from ${} up to full at ${} lost",
+                trigger.op, trigger.min_equip_value, trigger.max_equip_value
+            ));
+        }
+
+        let trigger = &self.low_money;
+        if trigger.enabled {
+            lines.push(format!(
+                "Low money: {:?} {}% for {}s below ${}",
+                trigger.op, trigger.intensity, trigger.duration, trigger.threshold
+            ));
+        }
+
+        for modifier in &self.weapon_modifiers {
+            let eco = modifier
+                .max_equip_value
+                .map(|max| format!(" up to ${} equip value", max))
+                .unwrap_or_default();
+            lines.push(format!(
+                "Killed holding {}: {}%{}",
+                modifier.pattern, modifier.scale, eco
+            ));
+        }
+
+        let defaults = GameModeFilters::default();
+        for ((name, filter), (_, default)) in
+            self.game_modes.iter().into_iter().zip(defaults.iter())
+        {
+            if !filter.enabled {
+                lines.push(format!("{}: off", name));
+            } else if filter != default {
+                let mut parts = vec![format!("{}%", filter.scale)];
+                for (on, trigger) in [
+                    (filter.death, "death"),
+                    (filter.expensive_death, "expensive death"),
+                    (filter.low_money, "low money"),
+                ] {
+                    if !on {
+                        parts.push(format!("no {}", trigger));
+                    }
+                }
+                lines.push(format!("{}: {}", name, parts.join(", ")));
+            }
+        }
+
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::engine::general_purpose::URL_SAFE;
+
+    use super::*;
+    use crate::config::{FollowMode, TriggerOp};
+
+    fn preset() -> Preset {
+        let mut preset = Preset {
+            min_intensity: 10,
+            max_intensity: 60,
+            max_duration: 4,
+            beep_on_round_start: true,
+            weapon_modifiers: vec![WeaponModifier {
+                pattern: "awp".to_owned(),
+                scale: 150,
+                max_equip_value: Some(1000),
+            }],
+            ..Preset::default()
+        };
+        preset.low_money.enabled = true;
+        preset.low_money.op = TriggerOp::Vibrate;
+        preset.game_modes.deathmatch.enabled = false;
+        preset
+    }
+
+    #[test]
+    fn code_round_trips() {
+        let preset = preset();
+        let code = preset.to_code();
+
+        assert!(code.starts_with(CODE_PREFIX));
+        assert!(!code.contains('='));
+        assert_eq!(Preset::parse(&code), Ok(preset));
+    }
+
+    #[test]
+    fn padded_codes_parse() {
+        let preset = preset();
+        let json = serde_json::to_string(&preset).unwrap();
+
+        for padded in [
+            format!("{}{}", CODE_PREFIX, URL_SAFE.encode(&json)),
+            format!("{}==", preset.to_code()),
+        ] {
+            assert_eq!(Preset::parse(&padded), Ok(preset.clone()));
+        }
+    }
+
+    #[test]
+    fn pasted_codes_are_trimmed() {
+        let preset = preset();
+
+        assert_eq!(
+            Preset::parse(&format!("  {}\n", preset.to_code())),
+            Ok(preset)
+        );
+    }
+
+    #[test]
+    fn json_round_trips() {
+        let preset = preset();
+
+        assert_eq!(Preset::parse(&preset.to_json()), Ok(preset));
+    }
+
+    #[test]
+    fn missing_settings_are_defaults() {
+        let preset = Preset::parse(r#"{ "max_intensity": 30 }"#).unwrap();
+
+        assert_eq!(
+            preset,
+            Preset {
+                max_intensity: 30,
+                ..Preset::default()
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_input_is_rejected() {
+        assert!(Preset::parse("cs2shock:not base64!").is_err());
+        assert!(Preset::parse(&format!(
+            "{}{}",
+            CODE_PREFIX,
+            URL_SAFE_NO_PAD.encode("nope")
+        ))
+        .is_err());
+        assert!(Preset::parse(&format!(
+            "{}{}",
+            CODE_PREFIX,
+            URL_SAFE_NO_PAD.encode([0xff, 0xfe])
+        ))
+        .is_err());
+        assert!(Preset::parse("").is_err());
+        assert!(Preset::parse("[1, 2]").is_err());
+    }
+
+    #[test]
+    fn credentials_are_never_shared() {
+        let config = Config {
+            username: "user".to_owned().into(),
+            code: "share code".to_owned().into(),
+            apikey: "api key".to_owned().into(),
+            api_token: "token".to_owned().into(),
+            ..Config::default()
+        };
+        let preset = Preset::from_config(&config);
+
+        for shared in [preset.to_json(), preset.to_code()] {
+            let shared = Preset::parse(&shared)
+                .map(|preset| preset.to_json())
+                .unwrap();
+            for secret in ["user", "share code", "api key", "token"] {
+                assert!(!shared.contains(secret), "{} leaked", secret);
+            }
+        }
+    }
+
+    #[test]
+    fn apply_keeps_everything_else() {
+        let config = Config {
+            username: "user".to_owned().into(),
+            follow: FollowMode::Player("76561198000000000".to_owned()),
+            profile: "stream".to_owned(),
+            ..Config::default()
+        };
+        let preset = preset();
+        let applied = preset.apply_to(&config);
+
+        assert_eq!(Preset::from_config(&applied), preset);
+        assert_eq!(*applied.username, "user");
+        assert_eq!(applied.follow, config.follow);
+        assert_eq!(applied.profile, "stream");
+        assert_eq!(applied.recording, config.recording);
+    }
+
+    #[test]
+    fn validate_reports_invalid_presets() {
+        let invalid = Preset {
+            min_intensity: 80,
+            max_intensity: 20,
+            ..Preset::default()
+        };
+        let errors = invalid.validate(&Config::default());
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "intensity");
+        assert!(preset().validate(&Config::default()).is_empty());
+    }
+
+    #[test]
+    fn describe_lists_enabled_triggers() {
+        let lines = preset().describe();
+
+        assert_eq!(lines[0], "Death: shock 10-60% for 1-4s");
+        assert!(lines.contains(&"Beep on round start".to_owned()));
+        assert!(lines
+            .iter()
+            .any(|line| line.starts_with("Low money: Vibrate")));
+        assert!(lines
+            .iter()
+            .any(|line| line == "Killed holding awp: 150% up to $1000 equip value"));
+        assert!(lines.contains(&"Deathmatch: off".to_owned()));
+        assert!(!lines.iter().any(|line| line.starts_with("Expensive death")));
+    }
+}
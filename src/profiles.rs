@@ -0,0 +1,179 @@
+//! Named profiles, stored as `profiles/<name>.json` next to the config file.
+//!
+//! A profile only holds the settings that differ from the config file, which
+//! keeps the credentials and the defaults for every profile. The active
+//! profile is saved in the config file as `profile`.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::Ordering,
+};
+
+use serde_json::{Map, Value};
+
+use crate::{config::Config, paths, AppState};
+
+/// Settings shared by every profile, these are never stored in a profile.
+const SHARED: [&str; 6] = [
+    "version",
+    "profile",
+    "username",
+    "code",
+    "apikey",
+    "api_token",
+];
+
+/// Where the profiles for a config file are stored.
+pub fn directory(config_path: &Path) -> PathBuf {
+    match config_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.join("profiles"),
+        _ => PathBuf::from("profiles"),
+    }
+}
+
+/// Profile names double as file names, keep them simple.
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 32
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Names of every saved profile, sorted.
+pub fn list(config_path: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(directory(config_path)) else {
+        return Vec::new();
+    };
+
+    let mut names = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| Some(path.file_stem()?.to_str()?.to_owned()))
+        .filter(|name| valid_name(name))
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+fn profile_path(config_path: &Path, name: &str) -> PathBuf {
+    directory(config_path).join(format!("{}.json", name))
+}
+
+/// Layer profile `name` over `base`, an empty name means no profile.
+pub fn apply(config_path: &Path, base: &Config, name: &str) -> Result<Config, String> {
+    if name.is_empty() {
+        return Ok(Config {
+            profile: String::new(),
+            ..base.clone()
+        });
+    }
+    if !valid_name(name) {
+        return Err(format!("Invalid profile name {:?}", name));
+    }
+
+    let path = profile_path(config_path, name);
+    let raw = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read profile {}: {}", path.display(), e))?;
+    let overrides = serde_json::from_str::<Map<String, Value>>(&raw)
+        .map_err(|e| format!("Failed to parse profile {}: {}", path.display(), e))?;
+
+    let mut value = serde_json::to_value(base).map_err(|e| e.to_string())?;
+    if let Value::Object(config) = &mut value {
+        for (key, setting) in overrides {
+            if !SHARED.contains(&key.as_str()) {
+                config.insert(key, setting);
+            }
+        }
+        config.insert("profile".to_owned(), Value::from(name));
+    }
+
+    let mut config = serde_json::from_value::<Config>(value)
+        .map_err(|e| format!("Failed to parse profile {}: {}", path.display(), e))?;
+    config.fill_secrets(base);
+    Ok(config)
+}
+
+/// Load the config file with its active profile applied.
+pub fn load(config_path: &Path) -> Result<Option<Config>, String> {
+    match Config::load(config_path)? {
+        Some(base) => apply(config_path, &base, &base.profile).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Save a config, shared settings go to the config file and everything else
+/// to the active profile, if there is one.
+pub fn save(config_path: &Path, config: &Config) -> Result<(), String> {
+    if config.profile.is_empty() {
+        return config.write_to_file(config_path);
+    }
+
+    let mut base = Config::load(config_path)?.unwrap_or_else(|| config.clone());
+    base.profile = config.profile.clone();
+    base.username = config.username.clone();
+    base.code = config.code.clone();
+    base.apikey = config.apikey.clone();
+    base.api_token = config.api_token.clone();
+    base.write_to_file(config_path)?;
+
+    let base = serde_json::to_value(&base).map_err(|e| e.to_string())?;
+    let overrides = match serde_json::to_value(config).map_err(|e| e.to_string())? {
+        Value::Object(settings) => settings
+            .into_iter()
+            .filter(|(key, setting)| {
+                !SHARED.contains(&key.as_str()) && base.get(key) != Some(setting)
+            })
+            .collect::<Map<_, _>>(),
+        _ => Map::new(),
+    };
+
+    let path = profile_path(config_path, &config.profile);
+    fs::create_dir_all(directory(config_path)).map_err(|e| e.to_string())?;
+    let json = serde_json::to_string_pretty(&overrides).map_err(|e| e.to_string())?;
+    paths::write_atomic(&path, json.as_bytes(), false)
+        .map_err(|e| format!("Failed to write profile {}: {}", path.display(), e))
+}
+
+/// Make `name` the profile the config file starts with, so later reloads
+/// keep using it. Nothing to do without a config file.
+pub fn remember(config_path: &Path, name: &str) -> Result<(), String> {
+    match Config::load(config_path)? {
+        Some(mut base) if base.profile != name => {
+            base.profile = name.to_owned();
+            base.write_to_file(config_path)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Switch the running instance to another profile, or back to the plain
+/// config file with an empty name, and remember it for the next start.
+pub async fn switch(state: &AppState, name: &str) -> Result<Config, String> {
+    let path = state
+        .config_path
+        .as_ref()
+        .ok_or_else(|| "Profiles need a config file".to_owned())?;
+
+    let mut current = state.config.write().await;
+    let base = Config::load(path)?.unwrap_or_else(|| current.clone());
+    let config = apply(path, &base, name)?;
+
+    let errors = config.validate();
+    if !errors.is_empty() {
+        let errors = errors
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        return Err(format!("Profile {} is invalid: {}", name, errors));
+    }
+
+    *current = config.clone();
+    state.config_revision.fetch_add(1, Ordering::Relaxed);
+    save(path, &current)?;
+
+    Ok(config)
+}
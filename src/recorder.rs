@@ -0,0 +1,160 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use time::{macros::format_description, OffsetDateTime};
+
+use crate::{config::RecordingConfig, feed::now_millis};
+
+/// One line of a recording, `payload` is the body exactly as CS2 sent it
+/// when it is valid JSON, or the raw string when it is not.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecordedPayload {
+    /// Milliseconds since the unix epoch
+    pub received_at: u64,
+    pub payload: Value,
+}
+
+/// Writes raw GSI payloads to JSON Lines files, one set of files per session.
+///
+/// Files are named `session-<start time>-<part>.jsonl`, a new part is started
+/// once the current one reaches the configured file size, and the oldest
+/// recordings are deleted to keep the directory under the total size cap.
+#[derive(Debug)]
+pub struct Recorder {
+    /// Relative recording directories are resolved next to this
+    config_path: Option<PathBuf>,
+    session: String,
+    part: u32,
+    file: Option<(PathBuf, File)>,
+    written: u64,
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl Recorder {
+    pub fn new(config_path: Option<PathBuf>) -> Self {
+        let session = OffsetDateTime::now_utc()
+            .format(format_description!(
+                "[year][month][day]-[hour][minute][second]"
+            ))
+            .unwrap_or_else(|_| "unknown".to_owned());
+
+        Self {
+            config_path,
+            session,
+            part: 0,
+            file: None,
+            written: 0,
+        }
+    }
+
+    /// Record a payload if recording is enabled, errors are logged and never
+    /// interrupt payload handling.
+    pub fn record(&mut self, config: &RecordingConfig, raw: &str) {
+        if !config.enabled {
+            // Start a fresh part when recording is toggled back on
+            self.file = None;
+            return;
+        }
+
+        if let Err(e) = self.write(config, raw) {
+            error!(target: "Recorder", "Failed to record payload: {}", e);
+            self.file = None;
+        }
+    }
+
+    fn write(&mut self, config: &RecordingConfig, raw: &str) -> io::Result<()> {
+        let received_at = now_millis();
+        let payload =
+            serde_json::from_str::<Value>(raw).unwrap_or_else(|_| Value::String(raw.to_owned()));
+
+        let mut line = serde_json::to_string(&RecordedPayload {
+            received_at,
+            payload,
+        })?;
+        line.push('\n');
+
+        let max_file_size = config.max_file_size_mb as u64 * 1024 * 1024;
+        if self.file.is_some() && self.written + line.len() as u64 > max_file_size {
+            self.file = None;
+            self.part += 1;
+        }
+
+        if self.file.is_none() {
+            let dir = config.path(self.config_path.as_deref());
+            fs::create_dir_all(&dir)?;
+
+            let path = dir.join(format!("session-{}-{}.jsonl", self.session, self.part));
+            let file = OpenOptions::new().create(true).append(true).open(&path)?;
+            self.written = file.metadata()?.len();
+            info!(target: "Recorder", "Recording payloads to {}", path.display());
+
+            self.file = Some((path, file));
+            self.enforce_cap(config)?;
+        }
+
+        if let Some((_, file)) = &mut self.file {
+            file.write_all(line.as_bytes())?;
+            self.written += line.len() as u64;
+        }
+
+        Ok(())
+    }
+
+    /// Delete the oldest recordings until the directory fits the total size cap.
+    fn enforce_cap(&self, config: &RecordingConfig) -> io::Result<()> {
+        let max_total_size = config.max_total_size_mb as u64 * 1024 * 1024;
+        let current = self.file.as_ref().map(|(path, _)| path.clone());
+
+        let mut files = fs::read_dir(config.path(self.config_path.as_deref()))?
+            .filter_map(Result::ok)
+            // Leave files we did not write alone, like recordings copied in
+            .filter(|entry| is_recording(&entry.path()))
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok()?;
+                let modified = metadata.modified().ok()?;
+                Some((modified, entry.path(), metadata.len()))
+            })
+            .collect::<Vec<_>>();
+        files.sort();
+
+        let mut total = files.iter().map(|(_, _, len)| len).sum::<u64>();
+        for (_, path, len) in files {
+            if total <= max_total_size {
+                break;
+            }
+            if Some(&path) == current.as_ref() {
+                continue;
+            }
+
+            match fs::remove_file(&path) {
+                Ok(_) => {
+                    info!(target: "Recorder", "Removed old recording {}", path.display());
+                    total -= len;
+                }
+                Err(e) => warn!(target: "Recorder", "Failed to remove {}: {}", path.display(), e),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether `path` is named like a recording the [`Recorder`] writes.
+fn is_recording(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "jsonl")
+        && path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("session-"))
+}
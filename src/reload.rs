@@ -0,0 +1,128 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::Ordering,
+    time::Duration,
+};
+
+use log::{error, info, warn};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+
+use crate::{profiles, secrets, AppState};
+
+/// Editors often write a file in several steps, wait for them to finish.
+const SETTLE_DELAY: Duration = Duration::from_millis(250);
+
+/// Watch the config file, secrets and profiles and swap in the new config whenever
+/// they are edited outside of cs2shock. Invalid edits are logged and ignored.
+pub fn spawn(state: AppState) {
+    let Some(path) = state.config_path.clone() else {
+        return;
+    };
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let watcher = notify::recommended_watcher(move |event| {
+        let _ = tx.send(event);
+    })
+    .and_then(|mut watcher: RecommendedWatcher| {
+        // Watch the directory, editors that save by renaming replace the file
+        watcher.watch(&directory(&path), RecursiveMode::NonRecursive)?;
+        Ok(watcher)
+    });
+
+    let mut watcher = match watcher {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            error!(target: "Config", "Failed to watch {}: {}", path.display(), e);
+            return;
+        }
+    };
+
+    let profiles = profiles::directory(&path);
+    let mut watching_profiles = watch_profiles(&mut watcher, &profiles);
+
+    tokio::spawn(async move {
+        // Dropping the watcher stops it, it lives as long as this task
+        while let Some(event) = rx.recv().await {
+            // The first profile saved creates the directory
+            if !watching_profiles {
+                watching_profiles = watch_profiles(&mut watcher, &profiles);
+            }
+
+            let touches_config = match event {
+                Ok(event) => event.paths.iter().any(|changed| {
+                    changed.file_name() == path.file_name()
+                        || changed.file_name() == secrets::path(&path).file_name()
+                        || changed
+                            .parent()
+                            .is_some_and(|parent| parent.ends_with("profiles"))
+                }),
+                Err(e) => {
+                    warn!(target: "Config", "Error watching {}: {}", path.display(), e);
+                    false
+                }
+            };
+            if !touches_config {
+                continue;
+            }
+
+            tokio::time::sleep(SETTLE_DELAY).await;
+            while rx.try_recv().is_ok() {}
+
+            reload(&state, &path).await;
+        }
+    });
+}
+
+/// Start watching the profiles directory if it exists, returns whether it is
+/// being watched.
+fn watch_profiles(watcher: &mut RecommendedWatcher, profiles: &Path) -> bool {
+    if !profiles.is_dir() {
+        return false;
+    }
+
+    match watcher.watch(profiles, RecursiveMode::NonRecursive) {
+        Ok(_) => true,
+        Err(e) => {
+            warn!(target: "Config", "Failed to watch {}: {}", profiles.display(), e);
+            false
+        }
+    }
+}
+
+fn directory(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_owned(),
+        _ => PathBuf::from("."),
+    }
+}
+
+async fn reload(state: &AppState, path: &Path) {
+    let config = match profiles::load(path) {
+        Ok(Some(config)) => config,
+        // Removed, keep running with what we have
+        Ok(None) => return,
+        Err(e) => {
+            error!(target: "Config", "Not reloading, {}", e);
+            return;
+        }
+    };
+
+    let errors = config.validate();
+    if !errors.is_empty() {
+        for error in errors {
+            error!(target: "Config", "Not reloading, {}", error);
+        }
+        return;
+    }
+
+    let mut current = state.config.write().await;
+    // Our own saves end up here as well
+    if *current == config {
+        return;
+    }
+
+    *current = config;
+    state.config_revision.fetch_add(1, Ordering::Relaxed);
+    info!(target: "Config", "Reloaded {}", path.display());
+}
@@ -0,0 +1,85 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+
+use clap::Args;
+use log::{info, warn};
+use tokio::sync::RwLock;
+
+use crate::{
+    api, backend::Backend, config::Config, gamestateintegration::Payload,
+    recorder::RecordedPayload, AppState,
+};
+
+#[derive(Args, Debug, Clone)]
+pub struct ReplayArgs {
+    /// Recording to replay, as written by the recorder
+    pub file: PathBuf,
+    /// Playback speed multiplier, 0 replays as fast as possible
+    #[arg(long, default_value_t = 1.0)]
+    pub speed: f64,
+    /// Send actions to the PiShock device instead of only logging them
+    #[arg(long)]
+    pub device: bool,
+}
+
+/// Feed a recorded session through the same pipeline as live payloads.
+pub async fn run(config: Arc<RwLock<Config>>, args: ReplayArgs) -> io::Result<()> {
+    let reader = BufReader::new(File::open(&args.file)?);
+
+    let backend = if args.device {
+        Backend::PiShock
+    } else {
+        Backend::DryRun
+    };
+    let state = AppState::new(config, backend, None);
+
+    info!(target: "Replay", "Replaying {} using {:?}", args.file.display(), state.backend);
+
+    let mut previous = None;
+    let mut replayed = 0;
+    let mut skipped = 0;
+
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let recorded = match serde_json::from_str::<RecordedPayload>(&line) {
+            Ok(recorded) => recorded,
+            Err(e) => {
+                warn!(target: "Replay", "Skipping line {}, not a recording: {}", i + 1, e);
+                skipped += 1;
+                continue;
+            }
+        };
+
+        if let Some(previous) = previous {
+            if args.speed > 0.0 {
+                let gap = recorded.received_at.saturating_sub(previous) as f64 / args.speed;
+                tokio::time::sleep(Duration::from_millis(gap as u64)).await;
+            }
+        }
+        previous = Some(recorded.received_at);
+
+        match serde_json::from_value::<Payload>(recorded.payload) {
+            Ok(payload) => {
+                api::process_payload(&state, payload).await;
+                replayed += 1;
+            }
+            Err(e) => {
+                warn!(target: "Replay", "Skipping line {}, invalid payload: {}", i + 1, e);
+                skipped += 1;
+            }
+        }
+    }
+
+    info!(target: "Replay", "Replayed {} payloads, skipped {}", replayed, skipped);
+
+    Ok(())
+}
@@ -0,0 +1,413 @@
+use log::info;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    config::{Config, ShockMode},
+    events::GameEvent,
+    gamestateintegration::GameMode,
+    pishock::PiShockOp,
+};
+
+/// Decide which device operations an event should cause under `config`
+/// while playing `mode`.
+pub fn evaluate(config: &Config, mode: &GameMode, event: &GameEvent) -> Vec<PiShockOp> {
+    let mode_filter = config.game_modes.get(mode);
+    if !mode_filter.enabled {
+        return Vec::new();
+    }
+
+    let mut ops = Vec::new();
+
+    match event {
+        GameEvent::MatchStarted if config.beep_on_match_start => {
+            ops.push(PiShockOp::Beep { duration: 2 });
+        }
+        GameEvent::RoundStarted if config.beep_on_round_start => {
+            ops.push(PiShockOp::Beep { duration: 1 });
+        }
+        // Everyone starts a half on the same money, that is no reason to punish
+        GameEvent::FreezetimeStarted {
+            money,
+            pistol_round: false,
+        } if config.low_money.enabled
+            && mode_filter.low_money
+            && *money < config.low_money.threshold =>
+        {
+            info!("Round starting with {} money, triggering low money", money);
+            let (intensity, duration) = config.scale_punishment(
+                config.low_money.intensity,
+                config.low_money.duration,
+                mode_filter.scale,
+            );
+            ops.push(config.low_money.op.with(intensity, duration));
+        }
+        GameEvent::Died {
+            last_health,
+            equip_value,
+            weapon,
+        } => {
+            let modifier = weapon
+                .as_ref()
+                .and_then(|weapon| config.weapon_modifier(weapon, *equip_value));
+
+            if let (Some(weapon), Some(modifier)) = (weapon, modifier) {
+                info!(
+                    "Died holding {}, scaling punishment to {}%",
+                    weapon.name, modifier.scale
+                );
+            }
+
+            let scale = mode_filter.scale * modifier.map_or(100, |modifier| modifier.scale) / 100;
+            if scale == 0 {
+                info!("Player died, punishment skipped");
+                return ops;
+            }
+
+            if mode_filter.death {
+                info!("Player died, shocking");
+
+                let (intensity, duration) = match config.shock_mode {
+                    ShockMode::Random => {
+                        let mut rng = StdRng::from_entropy();
+                        (
+                            rng.gen_range(config.min_intensity..=config.max_intensity),
+                            rng.gen_range(config.min_duration..=config.max_duration),
+                        )
+                    }
+                    ShockMode::LastHitPercentage => (
+                        (*last_health as f32 / 100.0 * config.max_intensity as f32) as i32,
+                        (*last_health as f32 / 100.0 * config.max_duration as f32) as i32,
+                    ),
+                };
+                let (intensity, duration) = config.scale_punishment(intensity, duration, scale);
+
+                ops.push(PiShockOp::Shock {
+                    intensity,
+                    duration,
+                });
+            }
+
+            let trigger = &config.expensive_death;
+            if trigger.enabled
+                && mode_filter.expensive_death
+                && *equip_value >= trigger.min_equip_value
+            {
+                info!(
+                    "Died with {} equip value, triggering expensive death",
+                    equip_value
+                );
+
+                let equip_scale = ((equip_value - trigger.min_equip_value) as f32
+                    / (trigger.max_equip_value - trigger.min_equip_value) as f32)
+                    .clamp(0.0, 1.0);
+                let intensity = config.min_intensity
+                    + ((config.max_intensity - config.min_intensity) as f32 * equip_scale) as i32;
+                let duration = config.min_duration
+                    + ((config.max_duration - config.min_duration) as f32 * equip_scale) as i32;
+                let (intensity, duration) = config.scale_punishment(intensity, duration, scale);
+
+                ops.push(trigger.op.with(intensity, duration));
+            }
+        }
+        _ => {}
+    }
+
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::{GameModeFilters, TriggerOp, WeaponModifier},
+        gamestateintegration::{Weapon, WeaponState},
+    };
+
+    /// A config where only the triggers a test turns on do anything.
+    fn config() -> Config {
+        let mut config = Config {
+            shock_mode: ShockMode::LastHitPercentage,
+            min_intensity: 10,
+            max_intensity: 50,
+            min_duration: 1,
+            max_duration: 5,
+            ..Config::default()
+        };
+        config.game_modes.competitive.death = false;
+        config
+    }
+
+    fn low_money(money: i32, pistol_round: bool) -> GameEvent {
+        GameEvent::FreezetimeStarted {
+            money,
+            pistol_round,
+        }
+    }
+
+    fn died(equip_value: i32) -> GameEvent {
+        GameEvent::Died {
+            last_health: 100,
+            equip_value,
+            weapon: None,
+        }
+    }
+
+    fn evaluate_competitive(config: &Config, event: &GameEvent) -> Vec<PiShockOp> {
+        evaluate(config, &GameMode::Competitive, event)
+    }
+
+    #[test]
+    fn low_money_below_the_threshold() {
+        let mut config = config();
+        config.low_money.enabled = true;
+        config.low_money.op = TriggerOp::Vibrate;
+        config.low_money.intensity = 30;
+        config.low_money.duration = 2;
+
+        assert_eq!(
+            evaluate_competitive(&config, &low_money(1999, false)),
+            [PiShockOp::Vibrate {
+                intensity: 30,
+                duration: 2
+            }]
+        );
+        assert!(evaluate_competitive(&config, &low_money(2000, false)).is_empty());
+    }
+
+    #[test]
+    fn low_money_skips_pistol_rounds() {
+        let mut config = config();
+        config.low_money.enabled = true;
+
+        assert!(evaluate_competitive(&config, &low_money(800, true)).is_empty());
+    }
+
+    #[test]
+    fn low_money_needs_to_be_enabled() {
+        assert!(evaluate_competitive(&config(), &low_money(0, false)).is_empty());
+    }
+
+    #[test]
+    fn expensive_death_scales_with_equip_value() {
+        let mut config = config();
+        config.expensive_death.enabled = true;
+        config.expensive_death.op = TriggerOp::Shock;
+
+        // Between 4000 and 8000 the punishment goes from min to max
+        for (equip_value, intensity, duration) in
+            [(4000, 10, 1), (6000, 30, 3), (8000, 50, 5), (12000, 50, 5)]
+        {
+            assert_eq!(
+                evaluate_competitive(&config, &died(equip_value)),
+                [PiShockOp::Shock {
+                    intensity,
+                    duration
+                }],
+                "equip value {}",
+                equip_value
+            );
+        }
+        assert!(evaluate_competitive(&config, &died(3999)).is_empty());
+    }
+
+    fn weapon(name: &str, kind: &str) -> Weapon {
+        Weapon {
+            name: name.to_owned(),
+            kind: kind.to_owned(),
+            state: WeaponState::Active,
+            ammo_clip: None,
+            ammo_clip_max: None,
+            ammo_reserve: None,
+        }
+    }
+
+    fn modifier(pattern: &str, scale: i32, max_equip_value: Option<i32>) -> WeaponModifier {
+        WeaponModifier {
+            pattern: pattern.to_owned(),
+            scale,
+            max_equip_value,
+        }
+    }
+
+    /// Death at full health holding `weapon`, which shocks at the max.
+    fn died_holding(weapon: Weapon, equip_value: i32) -> GameEvent {
+        GameEvent::Died {
+            last_health: 100,
+            equip_value,
+            weapon: Some(weapon),
+        }
+    }
+
+    /// `config` with death shocks on.
+    fn death_config(weapon_modifiers: Vec<WeaponModifier>) -> Config {
+        let mut config = config();
+        config.game_modes.competitive.death = true;
+        config.weapon_modifiers = weapon_modifiers;
+        config
+    }
+
+    #[test]
+    fn modifiers_match_names_with_or_without_prefix_and_categories() {
+        let awp = weapon("weapon_awp", "SniperRifle");
+        let knife = weapon("weapon_knife_karambit", "Knife");
+
+        for pattern in ["awp", "weapon_awp", "AWP", " awp ", "sniperrifle"] {
+            assert!(
+                modifier(pattern, 50, None).matches(&awp, 4700),
+                "{}",
+                pattern
+            );
+        }
+        assert!(modifier("Knife", 50, None).matches(&knife, 0));
+        assert!(!modifier("knife", 50, None).matches(&awp, 4700));
+        assert!(!modifier("aw", 50, None).matches(&awp, 4700));
+    }
+
+    #[test]
+    fn modifiers_scale_the_death_shock() {
+        let config = death_config(vec![modifier("awp", 50, None)]);
+
+        assert_eq!(
+            evaluate_competitive(&config, &died_holding(weapon("weapon_awp", ""), 4700)),
+            [PiShockOp::Shock {
+                intensity: 25,
+                duration: 2
+            }]
+        );
+        assert_eq!(
+            evaluate_competitive(&config, &died_holding(weapon("weapon_ak47", ""), 2700)),
+            [PiShockOp::Shock {
+                intensity: 50,
+                duration: 5
+            }]
+        );
+    }
+
+    #[test]
+    fn the_first_matching_modifier_wins() {
+        let config = death_config(vec![
+            modifier("Pistol", 20, None),
+            modifier("glock", 80, None),
+        ]);
+
+        assert_eq!(
+            evaluate_competitive(
+                &config,
+                &died_holding(weapon("weapon_glock", "Pistol"), 200)
+            ),
+            [PiShockOp::Shock {
+                intensity: 10,
+                duration: 1
+            }]
+        );
+    }
+
+    #[test]
+    fn eco_modifiers_need_a_cheap_loadout() {
+        let config = death_config(vec![modifier("Pistol", 0, Some(1000))]);
+        let glock = weapon("weapon_glock", "Pistol");
+
+        assert!(evaluate_competitive(&config, &died_holding(glock.clone(), 1000)).is_empty());
+        assert_eq!(
+            evaluate_competitive(&config, &died_holding(glock, 1001)).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn modifiers_never_go_past_the_max() {
+        let config = death_config(vec![modifier("awp", 1000, None)]);
+
+        assert_eq!(
+            evaluate_competitive(&config, &died_holding(weapon("weapon_awp", ""), 4700)),
+            [PiShockOp::Shock {
+                intensity: config.max_intensity,
+                duration: config.max_duration
+            }]
+        );
+    }
+
+    #[test]
+    fn zero_scale_skips_every_death_punishment() {
+        let mut config = death_config(vec![modifier("knife", 0, None)]);
+        config.expensive_death.enabled = true;
+
+        let event = died_holding(weapon("weapon_knife", "Knife"), 9000);
+        assert!(evaluate_competitive(&config, &event).is_empty());
+    }
+
+    #[test]
+    fn disabled_modes_do_nothing_at_all() {
+        let mut config = death_config(Vec::new());
+        config.beep_on_round_start = true;
+        config.game_modes.casual.enabled = false;
+
+        for event in [GameEvent::RoundStarted, died(0)] {
+            assert!(evaluate(&config, &GameMode::Casual, &event).is_empty());
+            assert!(!evaluate(&config, &GameMode::Competitive, &event).is_empty());
+        }
+    }
+
+    #[test]
+    fn mode_scale_applies_to_punishments() {
+        let mut config = death_config(Vec::new());
+        config.game_modes.wingman.scale = 50;
+
+        assert_eq!(
+            evaluate(&config, &GameMode::Wingman, &died(0)),
+            [PiShockOp::Shock {
+                intensity: 25,
+                duration: 2
+            }]
+        );
+    }
+
+    #[test]
+    fn zero_mode_scale_keeps_beeps() {
+        let mut config = death_config(Vec::new());
+        config.beep_on_round_start = true;
+        config.game_modes.other.scale = 0;
+
+        assert!(evaluate(&config, &GameMode::Other, &died(0)).is_empty());
+        assert_eq!(
+            evaluate(&config, &GameMode::Other, &GameEvent::RoundStarted),
+            [PiShockOp::Beep { duration: 1 }]
+        );
+    }
+
+    #[test]
+    fn modes_switch_triggers_separately() {
+        let mut config = death_config(Vec::new());
+        config.expensive_death.enabled = true;
+        config.low_money.enabled = true;
+        let filter = &mut config.game_modes.arms_race;
+        filter.scale = 100;
+        filter.death = false;
+        filter.expensive_death = true;
+        filter.low_money = false;
+
+        assert_eq!(
+            evaluate(&config, &GameMode::ArmsRace, &died(8000)),
+            [PiShockOp::Vibrate {
+                intensity: 50,
+                duration: 5
+            }]
+        );
+        assert!(evaluate(&config, &GameMode::ArmsRace, &low_money(0, false)).is_empty());
+    }
+
+    #[test]
+    fn deathmatch_defaults_to_half_and_deaths_only() {
+        let mut config = death_config(Vec::new());
+        config.game_modes.deathmatch = GameModeFilters::default().deathmatch;
+        config.expensive_death.enabled = true;
+
+        assert_eq!(
+            evaluate(&config, &GameMode::Deathmatch, &died(8000)),
+            [PiShockOp::Shock {
+                intensity: 25,
+                duration: 2
+            }]
+        );
+    }
+}
@@ -0,0 +1,152 @@
+//! PiShock credentials and the control API token, kept out of `config.json`.
+//!
+//! They are stored in `secrets.json` next to the config file, readable only
+//! by the current user on Unix. Each one can be overridden with an
+//! environment variable, overridden values are never written to disk.
+
+use std::{
+    env, fmt, fs, io,
+    ops::{Deref, DerefMut},
+    path::{Path, PathBuf},
+};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::Config, paths};
+
+pub const USERNAME_VAR: &str = "CS2SHOCK_USERNAME";
+pub const CODE_VAR: &str = "CS2SHOCK_CODE";
+pub const APIKEY_VAR: &str = "CS2SHOCK_APIKEY";
+pub const API_TOKEN_VAR: &str = "CS2SHOCK_API_TOKEN";
+
+/// A string that is redacted when debug printed, so logging a config never
+/// leaks it.
+#[derive(Deserialize, Serialize, Clone, Default, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            write!(f, "\"\"")
+        } else {
+            write!(f, "\"<redacted>\"")
+        }
+    }
+}
+
+impl Deref for Secret {
+    type Target = String;
+
+    fn deref(&self) -> &String {
+        &self.0
+    }
+}
+
+impl DerefMut for Secret {
+    fn deref_mut(&mut self) -> &mut String {
+        &mut self.0
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+/// Contents of `secrets.json`.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(default)]
+struct SecretsFile {
+    username: Secret,
+    code: Secret,
+    apikey: Secret,
+    api_token: Secret,
+}
+
+/// Where the secrets for a config file are stored.
+pub fn path(config_path: &Path) -> PathBuf {
+    match config_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.join("secrets.json"),
+        _ => PathBuf::from("secrets.json"),
+    }
+}
+
+fn read(path: &Path) -> Result<Option<SecretsFile>, String> {
+    match fs::read_to_string(path) {
+        Ok(raw) => serde_json::from_str(&raw)
+            .map(Some)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+/// Fill in the secrets of a freshly loaded config from `secrets.json`.
+/// Secrets still in an old `config.json` are kept until the secrets file
+/// exists.
+pub fn load(config_path: &Path, config: &mut Config) -> Result<(), String> {
+    if let Some(secrets) = read(&path(config_path))? {
+        config.username = secrets.username;
+        config.code = secrets.code;
+        config.apikey = secrets.apikey;
+        config.api_token = secrets.api_token;
+    }
+
+    Ok(())
+}
+
+/// Override secrets with the ones set in the environment.
+pub fn apply_env(config: &mut Config) {
+    for (var, secret) in [
+        (USERNAME_VAR, &mut config.username),
+        (CODE_VAR, &mut config.code),
+        (APIKEY_VAR, &mut config.apikey),
+        (API_TOKEN_VAR, &mut config.api_token),
+    ] {
+        if let Ok(value) = env::var(var) {
+            info!(target: "Secrets", "Using {} from the environment", var);
+            *secret = Secret(value);
+        }
+    }
+}
+
+/// Write the secrets of a config to `secrets.json`, values that came from the
+/// environment keep whatever the file had.
+pub fn save(config_path: &Path, config: &Config) -> Result<(), String> {
+    let path = path(config_path);
+    let existing = read(&path).ok().flatten().unwrap_or_default();
+
+    let pick = |var: &str, current: &Secret, existing: Secret| {
+        if env::var(var).is_ok_and(|value| value == **current) {
+            existing
+        } else {
+            current.clone()
+        }
+    };
+    let secrets = SecretsFile {
+        username: pick(USERNAME_VAR, &config.username, existing.username),
+        code: pick(CODE_VAR, &config.code, existing.code),
+        apikey: pick(APIKEY_VAR, &config.apikey, existing.apikey),
+        api_token: pick(API_TOKEN_VAR, &config.api_token, existing.api_token),
+    };
+
+    let json = serde_json::to_string_pretty(&secrets).map_err(|e| e.to_string())?;
+    paths::write_atomic(&path, json.as_bytes(), true)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Make an existing file readable only by the current user, a no-op where
+/// that is not supported.
+pub fn restrict(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
@@ -0,0 +1,276 @@
+use std::time::Duration;
+
+use clap::{Args, ValueEnum};
+use log::{error, info};
+use serde_json::{json, Value};
+
+pub const DEFAULT_URL: &str = "http://127.0.0.1:3000/data";
+
+/// A single change to the simulated game, posted as one payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimStep {
+    Warmup,
+    Live,
+    Freezetime,
+    RoundLive,
+    Damage(i32),
+    Die,
+    RoundOver,
+    GameOver,
+    Leave,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scenario {
+    /// Warmup, go live, take 40 damage, die and lose the round
+    Death,
+    /// Three rounds with a death each, then game over and back to the menu
+    Match,
+}
+
+impl Scenario {
+    pub fn steps(&self) -> Vec<SimStep> {
+        let round = [
+            SimStep::Freezetime,
+            SimStep::RoundLive,
+            SimStep::Damage(40),
+            SimStep::Die,
+            SimStep::RoundOver,
+        ];
+
+        match self {
+            Scenario::Death => [&[SimStep::Warmup, SimStep::Live][..], &round].concat(),
+            Scenario::Match => [
+                &[SimStep::Warmup, SimStep::Live][..],
+                &round,
+                &round,
+                &round,
+                &[SimStep::GameOver, SimStep::Leave],
+            ]
+            .concat(),
+        }
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct SimulateArgs {
+    /// Scripted scenario to post
+    #[arg(long, value_enum, default_value_t = Scenario::Death)]
+    pub scenario: Scenario,
+    /// GSI endpoint to post to
+    #[arg(long, default_value = DEFAULT_URL)]
+    pub url: String,
+    /// Delay between payloads in milliseconds
+    #[arg(long, default_value_t = 1000)]
+    pub delay: u64,
+}
+
+/// Synthetic CS2 client posting GSI payloads to a running cs2shock.
+#[derive(Debug, Clone)]
+pub struct Simulator {
+    pub url: String,
+    pub steamid: String,
+    pub map: Option<String>,
+    pub mode: String,
+    pub map_phase: String,
+    pub round_phase: String,
+    /// Rounds played so far
+    pub round: i32,
+    pub health: i32,
+    pub armor: i32,
+    pub money: i32,
+    pub equip_value: i32,
+    pub kills: i32,
+    pub deaths: i32,
+    pub weapon: String,
+    /// Outcome of the most recent post
+    pub last_result: Option<Result<(), String>>,
+    timestamp: i64,
+}
+
+impl Default for Simulator {
+    fn default() -> Self {
+        Self::new(DEFAULT_URL)
+    }
+}
+
+impl Simulator {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_owned(),
+            steamid: "76561197960265728".to_owned(),
+            map: None,
+            mode: "competitive".to_owned(),
+            map_phase: "warmup".to_owned(),
+            round_phase: "freezetime".to_owned(),
+            round: 0,
+            health: 100,
+            armor: 100,
+            money: 800,
+            equip_value: 200,
+            kills: 0,
+            deaths: 0,
+            weapon: "weapon_glock".to_owned(),
+            last_result: None,
+            timestamp: 0,
+        }
+    }
+
+    pub fn apply(&mut self, step: SimStep) {
+        match step {
+            SimStep::Warmup => {
+                self.map = Some("de_dust2".to_owned());
+                self.map_phase = "warmup".to_owned();
+                self.round_phase = "live".to_owned();
+                self.round = 0;
+                self.deaths = 0;
+                self.kills = 0;
+            }
+            SimStep::Live => {
+                self.map_phase = "live".to_owned();
+                self.round = 0;
+                self.deaths = 0;
+                self.kills = 0;
+            }
+            SimStep::Freezetime => {
+                self.round_phase = "freezetime".to_owned();
+                self.health = 100;
+                self.armor = 100;
+                self.equip_value = 200;
+            }
+            SimStep::RoundLive => self.round_phase = "live".to_owned(),
+            SimStep::Damage(amount) => self.health = (self.health - amount).max(1),
+            SimStep::Die => {
+                self.health = 0;
+                self.armor = 0;
+                self.deaths += 1;
+            }
+            SimStep::RoundOver => {
+                self.round_phase = "over".to_owned();
+                self.round += 1;
+                self.money = (self.money + 1900).min(16000);
+            }
+            SimStep::GameOver => self.map_phase = "gameover".to_owned(),
+            SimStep::Leave => self.map = None,
+        }
+    }
+
+    pub fn payload(&self) -> Value {
+        let mut payload = json!({
+            "provider": {
+                "name": "Counter-Strike 2",
+                "appid": 730,
+                "version": 14000,
+                "steamid": self.steamid,
+                "timestamp": self.timestamp,
+            },
+        });
+
+        if let Some(map) = &self.map {
+            let weapons = if self.health > 0 {
+                json!({
+                    "weapon_0": { "name": "weapon_knife", "type": "Knife", "state": "holstered" },
+                    "weapon_1": { "name": self.weapon, "type": "Pistol", "state": "active" },
+                })
+            } else {
+                json!({})
+            };
+
+            payload["map"] = json!({
+                "mode": self.mode,
+                "name": map,
+                "phase": self.map_phase,
+                "round": self.round,
+            });
+            payload["round"] = json!({ "phase": self.round_phase });
+            payload["player"] = json!({
+                "steamid": self.steamid,
+                "name": "Simulator",
+                "state": {
+                    "health": self.health,
+                    "armor": self.armor,
+                    "helmet": false,
+                    "flashed": 0,
+                    "smoked": 0,
+                    "burning": 0,
+                    "money": self.money,
+                    "round_kills": 0,
+                    "round_killhs": 0,
+                    "equip_value": self.equip_value,
+                },
+                "match_stats": {
+                    "kills": self.kills,
+                    "assists": 0,
+                    "deaths": self.deaths,
+                    "mvps": 0,
+                    "score": 0,
+                },
+                "weapons": weapons,
+            });
+        } else {
+            // The main menu still names the player, without any state
+            payload["player"] = json!({
+                "steamid": self.steamid,
+                "name": "Simulator",
+                "activity": "menu",
+            });
+        }
+
+        payload
+    }
+
+    /// Post the current state, failing unless the server accepted it.
+    pub async fn post(&mut self) -> Result<(), String> {
+        self.timestamp += 1;
+
+        let res = reqwest::Client::new()
+            .post(&self.url)
+            .json(&self.payload())
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if res.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!("Server responded with {}", res.status().as_u16()))
+        }
+    }
+
+    pub async fn step(&mut self, step: SimStep) -> Result<(), String> {
+        info!(target: "Simulator", "Posting {:?}", step);
+        self.apply(step);
+        let result = self.post().await;
+        self.last_result = Some(result.clone());
+        result
+    }
+
+    /// Post every step of a scenario, stopping at the first failure.
+    pub async fn run_scenario(
+        &mut self,
+        scenario: Scenario,
+        delay: Duration,
+    ) -> Result<(), String> {
+        for step in scenario.steps() {
+            self.step(step)
+                .await
+                .map_err(|e| format!("Failed to post {:?}: {}", step, e))?;
+            tokio::time::sleep(delay).await;
+        }
+
+        Ok(())
+    }
+}
+
+/// Post a scripted scenario, logging the outcome.
+pub async fn run(args: SimulateArgs) {
+    let mut simulator = Simulator::new(&args.url);
+
+    match simulator
+        .run_scenario(args.scenario, Duration::from_millis(args.delay))
+        .await
+    {
+        Ok(_) => info!(target: "Simulator", "Scenario {:?} completed", args.scenario),
+        Err(e) => error!(target: "Simulator", "{}", e),
+    }
+}
@@ -0,0 +1,96 @@
+use std::{
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+
+use axum::{extract::State, Json};
+use serde::Serialize;
+
+use crate::{backend::Backend, feed::now_millis, AppState};
+
+/// CS2 is considered disconnected after this long without a payload, the
+/// bundled cfg asks for a heartbeat every 10 seconds.
+pub const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Connection health, updated as payloads arrive and actions are sent.
+#[derive(Debug, Default)]
+pub struct Health {
+    last_payload: Option<Instant>,
+    /// Whether the last action that reached the backend went through,
+    /// `None` until something has been sent
+    backend_reachable: Option<bool>,
+    last_error: Option<LastError>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct LastError {
+    /// Milliseconds since the unix epoch
+    pub time: u64,
+    pub message: String,
+}
+
+impl Health {
+    pub fn payload_received(&mut self) {
+        self.last_payload = Some(Instant::now());
+    }
+
+    pub fn backend_result(&mut self, result: &Result<(), String>) {
+        self.backend_reachable = Some(result.is_ok());
+        if let Err(e) = result {
+            self.error(e.clone());
+        }
+    }
+
+    pub fn error(&mut self, message: String) {
+        self.last_error = Some(LastError {
+            time: now_millis(),
+            message,
+        });
+    }
+
+    /// Time since the last payload, `None` if nothing has arrived yet.
+    pub fn since_last_payload(&self) -> Option<Duration> {
+        self.last_payload.map(|instant| instant.elapsed())
+    }
+
+    pub fn connected(&self) -> bool {
+        self.since_last_payload()
+            .is_some_and(|since| since < HEARTBEAT_TIMEOUT)
+    }
+
+    pub fn backend_reachable(&self) -> Option<bool> {
+        self.backend_reachable
+    }
+
+    pub fn last_error(&self) -> Option<&LastError> {
+        self.last_error.as_ref()
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct StatusResponse {
+    /// Whether CS2 has sent anything within [`HEARTBEAT_TIMEOUT`]
+    pub connected: bool,
+    pub seconds_since_last_payload: Option<f64>,
+    pub backend: &'static str,
+    pub backend_reachable: Option<bool>,
+    pub paused: bool,
+    pub last_error: Option<LastError>,
+}
+
+/// `GET /status`, whether CS2 is sending data and the backend is working.
+pub async fn status(State(state): State<AppState>) -> Json<StatusResponse> {
+    let health = state.health.lock().await;
+
+    Json(StatusResponse {
+        connected: health.connected(),
+        seconds_since_last_payload: health.since_last_payload().map(|since| since.as_secs_f64()),
+        backend: match state.backend {
+            Backend::PiShock => "pishock",
+            Backend::DryRun => "dry_run",
+        },
+        backend_reachable: health.backend_reachable(),
+        paused: state.paused.load(Ordering::Relaxed),
+        last_error: health.last_error().cloned(),
+    })
+}